@@ -83,6 +83,24 @@ impl SocketStream {
         })
     }
 
+    /// Uid of the process on the other end of the socket
+    pub fn peer_uid(&self) -> Result<u32> {
+        let cred = self
+            .stream
+            .peer_cred()
+            .context("failed to get peer credentials")?;
+        Ok(cred.uid())
+    }
+
+    /// Pid of the process on the other end of the socket, if it is known
+    pub fn peer_pid(&self) -> Result<Option<u32>> {
+        let cred = self
+            .stream
+            .peer_cred()
+            .context("failed to get peer credentials")?;
+        Ok(cred.pid().map(|pid| pid as u32))
+    }
+
     pub async fn send<T: for<'a> serde::Serialize>(&mut self, msg: T) -> Result<()> {
         let bytes = rmp_serde::to_vec(&msg).with_context(|| "failed to serialize message")?;
         self.stream
@@ -0,0 +1,61 @@
+use crate::noise::NoiseConfig;
+use anyhow::{Context, Result, bail};
+use blink_timer::input::devices::DeviceFilter;
+use serde::Deserialize;
+use std::{ffi::CString, fs, path::Path};
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Users that can always connect, besides root and the user of the active session
+    pub allowed_users: Vec<User>,
+    /// Input devices that are tracked
+    pub devices: DeviceFilter,
+    /// Thresholds for the movement of absolute axes to count as input
+    pub noise: NoiseConfig,
+}
+
+/// A user given by its uid or name
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum User {
+    Uid(u32),
+    Name(String),
+}
+
+impl Config {
+    /// Loads the config file, the defaults are used if it does not exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            log::info!("No config at '{}', using defaults", path.display());
+            return Ok(Self::default());
+        }
+        let config_str = fs::read_to_string(path).context("failed to read config file")?;
+        let config = serde_yaml_ng::from_str(&config_str).context("failed to parse config file")?;
+        log::info!("Loaded config from '{}'", path.display());
+        Ok(config)
+    }
+
+    /// Resolves the uids of the allowed users
+    pub fn allowed_uids(&self) -> Result<Vec<u32>> {
+        self.allowed_users.iter().map(User::uid).collect()
+    }
+}
+
+impl User {
+    fn uid(&self) -> Result<u32> {
+        match self {
+            User::Uid(uid) => Ok(*uid),
+            User::Name(name) => {
+                let c_name = CString::new(name.as_str()).context("invalid user name")?;
+                // SAFETY: getpwnam is called with a valid C string and the result is read before
+                // any other call that could overwrite it
+                let passwd = unsafe { libc::getpwnam(c_name.as_ptr()) };
+                if passwd.is_null() {
+                    bail!("unknown user '{name}'");
+                }
+                Ok(unsafe { (*passwd).pw_uid })
+            }
+        }
+    }
+}
@@ -0,0 +1,424 @@
+/// actived is a daemon that determines if a user is 'active' or not by listening to input events.
+/// It is intended to be ran seperately since it needs root permissions to access input devices.
+use anyhow::{Context, Result};
+use blink_timer::{
+    ACTIVED_PROTOCOL_VERSION, ActivedHello, ActivityMessage, InputKind,
+    async_socket::{SocketServer, SocketStream},
+    get_unix_time_ms,
+    input::{
+        devices::{DeviceFilter, DeviceInfo},
+        source::{EvdevSource, InputSource, SourceEvent},
+    },
+};
+use clap::Parser;
+use config::Config;
+use evdev::InputEvent;
+use noise::{NoiseConfig, NoiseGate};
+use replay::ReplaySource;
+use session::Access;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    process,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::broadcast;
+
+mod config;
+mod noise;
+mod replay;
+mod session;
+
+// Minimum time between emitting events, in ms
+const EVENT_COOLDOWN_MS: u64 = 500;
+// Time in which a client has to send its hello
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Set a custom config file, defaults to /etc/blink/actived.yaml
+    #[clap(short, long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// List the input devices and whether they are tracked, then exit
+    #[clap(long)]
+    list_devices: bool,
+    /// Replay the devices and input of a trace file instead of reading the input devices
+    #[clap(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .init();
+    let args = Args::parse();
+
+    let config_path = args.config.unwrap_or_else(blink_timer::actived_config_path);
+    let config = Config::load(&config_path)?;
+    if args.list_devices {
+        list_devices(&config.devices);
+        return Ok(());
+    }
+    let trace = args.replay.as_deref().map(replay::read_trace).transpose()?;
+    let access = Access::new(config.allowed_uids()?).await;
+    tokio::spawn(access.clone().watch_active_sessions());
+
+    // Broadcast channel for distributing events to all clients, along with their seat
+    let (broadcast_tx, _) = broadcast::channel::<(String, ActivityMessage)>(100);
+
+    // Last input timestamps
+    let activity = Arc::new(Mutex::new(SeatActivity::new(get_unix_time_ms())));
+
+    // Start input listener
+    tokio::spawn({
+        let activity = activity.clone();
+        let broadcast_tx = broadcast_tx.clone();
+
+        async move {
+            let result = match trace {
+                Some(trace) => {
+                    log::info!("Replaying {} trace entries", trace.len());
+                    let source = ReplaySource::new(trace, get_unix_time_ms(), true);
+                    run_input_listener(source, config.devices, config.noise, broadcast_tx, activity)
+                        .await
+                }
+                None => match EvdevSource::new() {
+                    Ok(source) => {
+                        let (devices, noise) = (config.devices, config.noise);
+                        run_input_listener(source, devices, noise, broadcast_tx, activity).await
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+            match result {
+                Ok(()) => log::info!("Replay finished, no more input will be received"),
+                Err(e) => {
+                    log::error!("Event listener failed: {e}");
+                    process::exit(1);
+                }
+            }
+        }
+    });
+
+    let mut socket_server = SocketServer::create(blink_timer::actived_socket_path(), true)
+        .await
+        .context("Failed to create socket server")?;
+    log::info!("listening for client connections");
+
+    // Accept and handle clients
+    loop {
+        let stream = socket_server.accept_client().await?;
+        let broadcast_rx = broadcast_tx.subscribe();
+        let activity = activity.clone();
+        let access = access.clone();
+
+        // Spawn a new task for each client
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, broadcast_rx, activity, access).await {
+                log::error!("Client handler error: {e:?}");
+            }
+        });
+    }
+}
+
+/// Activity of the tracked devices per seat
+struct SeatActivity {
+    seats: HashMap<String, ActivityMessage>,
+    /// Unix time in ms at which actived started, used as the last input of a seat without input
+    started: u64,
+}
+
+impl SeatActivity {
+    fn new(started: u64) -> Self {
+        Self {
+            seats: HashMap::new(),
+            started,
+        }
+    }
+
+    fn get_mut(&mut self, seat: &str) -> &mut ActivityMessage {
+        self.seats
+            .entry(seat.to_string())
+            .or_insert_with(|| ActivityMessage::any(self.started))
+    }
+}
+
+async fn handle_client(
+    mut stream: SocketStream,
+    mut broadcast_rx: broadcast::Receiver<(String, ActivityMessage)>,
+    activity: Arc<Mutex<SeatActivity>>,
+    access: Access,
+) -> Result<()> {
+    let uid = stream.peer_uid()?;
+    let pid = stream.peer_pid()?;
+    let client = match pid {
+        Some(pid) => format!("uid {uid} and pid {pid}"),
+        None => format!("uid {uid}"),
+    };
+
+    // The client starts with a hello, which is answered such that it can check the version
+    let hello: ActivedHello = tokio::time::timeout(HELLO_TIMEOUT, stream.recv())
+        .await
+        .context("Client did not send a hello, it is probably older than actived")??;
+
+    // Only the activity of the seat of the client is sent. A refused client is disconnected
+    // without an answer to its hello, so it learns nothing about actived
+    let seat = access.seat_of(pid, uid).await;
+    if !access.is_allowed(uid, &seat) {
+        log::info!("Refused client with {client}, it does not own the active session on {seat}");
+        return Ok(());
+    }
+
+    stream.send(&ActivedHello::current()).await?;
+    if hello.version != ACTIVED_PROTOCOL_VERSION {
+        log::warn!(
+            "Refused client with {client}, it uses protocol version {} instead of {ACTIVED_PROTOCOL_VERSION}",
+            hello.version
+        );
+        return Ok(());
+    }
+    log::info!("Client with uid {uid} on {seat} connected");
+
+    // send initial value to the client
+    let initial = *activity.lock().unwrap().get_mut(&seat);
+    stream.send(&initial).await?;
+
+    // Listen for events and forward them to the client
+    loop {
+        let (message_seat, message) = match broadcast_rx.recv().await {
+            Ok(message) => message,
+            // The client sees the skipped updates as a gap in the sequence numbers
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        if message_seat != seat {
+            continue;
+        }
+        if !access.is_allowed(uid, &seat) {
+            log::info!(
+                "Disconnecting client with uid {uid}, it no longer owns the active session on {seat}"
+            );
+            return Ok(());
+        }
+        stream.send(&message).await?;
+    }
+}
+
+/// Input state of a tracked device
+struct TrackedDevice {
+    seat: String,
+    direct: bool,
+    noise_gate: Option<NoiseGate>,
+    /// Monotonic times at which the last update of each input kind was sent
+    last_emit: [Option<u64>; InputKind::ALL.len()],
+}
+
+impl TrackedDevice {
+    /// Counts the event, returns the activity when an update has to be sent
+    ///
+    /// The unix time is sent to the clients, the monotonic uptime is used for the intervals such
+    /// that they are not affected by changes of the clock
+    fn handle(
+        &mut self,
+        event: &InputEvent,
+        time: u64,
+        uptime: u64,
+        activity: &mut ActivityMessage,
+    ) -> Option<ActivityMessage> {
+        let kind = InputKind::of_event(event, self.direct)?;
+        if self
+            .noise_gate
+            .as_mut()
+            .is_some_and(|gate| !gate.accept(event, uptime))
+        {
+            return None;
+        }
+        // Every event is counted, but the update is only sent after the cooldown
+        activity.count(event, kind);
+
+        let last_emit = &mut self.last_emit[kind as usize];
+        if last_emit.is_some_and(|last_emit| uptime.saturating_sub(last_emit) < EVENT_COOLDOWN_MS) {
+            return None;
+        }
+        *last_emit = Some(uptime);
+        log::debug!("Input {kind:?} received at {time}");
+
+        activity.seq += 1;
+        activity.record(kind, time);
+        Some(*activity)
+    }
+}
+
+/// Tracks the devices of the source that pass the filter and broadcasts their activity,
+/// returns when the source has ended
+async fn run_input_listener(
+    mut source: impl InputSource,
+    filter: DeviceFilter,
+    noise: NoiseConfig,
+    broadcast_tx: broadcast::Sender<(String, ActivityMessage)>,
+    activity: Arc<Mutex<SeatActivity>>,
+) -> Result<()> {
+    let mut devices: HashMap<PathBuf, TrackedDevice> = HashMap::new();
+    while let Some(event) = source.next().await? {
+        match event {
+            SourceEvent::Added { path, info } => {
+                let verdict = filter.check(&info);
+                if !verdict.is_tracked() {
+                    log::debug!("Skipping '{}' ({}): {verdict}", path.display(), info.name);
+                    continue;
+                }
+                if let Err(e) = source.track(&path) {
+                    log::warn!("Failed to listen on '{}': {e:#}", path.display());
+                    continue;
+                }
+                log::info!(
+                    "Listening for events on '{}' ({}) on {}: {verdict}",
+                    path.display(),
+                    info.name,
+                    info.seat
+                );
+                let noise_gate = noise.for_device(&info).map(|filter| {
+                    log::debug!("Filtering noise of '{}': {filter:?}", path.display());
+                    NoiseGate::new(&info.axes, filter)
+                });
+                let device = TrackedDevice {
+                    seat: info.seat,
+                    direct: info.direct,
+                    noise_gate,
+                    last_emit: Default::default(),
+                };
+                devices.insert(path, device);
+            }
+            SourceEvent::Removed { path } => {
+                devices.remove(&path);
+            }
+            SourceEvent::Scanned => {
+                if devices.is_empty() {
+                    log::warn!(
+                        "No input devices are tracked! are you running as root? see 'actived --list-devices'"
+                    );
+                }
+            }
+            SourceEvent::Input {
+                path,
+                event,
+                time,
+                uptime,
+            } => {
+                let Some(device) = devices.get_mut(&path) else {
+                    continue;
+                };
+                let message = {
+                    let mut activity = activity.lock().unwrap();
+                    device.handle(&event, time, uptime, activity.get_mut(&device.seat))
+                };
+                if let Some(message) = message {
+                    let _ = broadcast_tx.send((device.seat.clone(), message));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints the input devices and whether they would be tracked
+fn list_devices(filter: &DeviceFilter) {
+    let mut devices: Vec<_> = evdev::enumerate().collect();
+    if devices.is_empty() {
+        println!("No input devices found! are you running as root?");
+        return;
+    }
+    devices.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (path, device) in devices {
+        let info = DeviceInfo::new(&path, &device);
+        let capabilities: Vec<_> = info.capabilities.iter().map(|c| c.to_string()).collect();
+        println!("{}: {}", path.display(), info.name);
+        println!("  id:           {:04x}:{:04x}", info.vendor, info.product);
+        println!("  phys:         {}", info.phys);
+        println!("  seat:         {}", info.seat);
+        println!("  capabilities: {}", capabilities.join(", "));
+        println!("  {}", filter.check(&info));
+    }
+}
+
+#[tokio::test]
+async fn replay_test() {
+    let trace = replay::parse_trace(
+        r#"
+{"time":0,"device":"event0","event":"added","info":{"name":"Keyboard","capabilities":["keyboard"]}}
+{"time":0,"device":"event1","event":"added","info":{"name":"Gamepad","capabilities":["joystick"]}}
+{"time":0,"device":"event2","event":"added","info":{"name":"Touchpad","capabilities":["touchpad"],"axes":[{"code":0,"minimum":0,"maximum":1000}]}}
+{"time":0,"device":"event3","event":"added","info":{"name":"Keyboard","capabilities":["keyboard"],"seat":"seat1"}}
+{"time":100,"device":"event0","event":"input","type":1,"code":30,"value":1}
+{"time":150,"device":"event3","event":"input","type":1,"code":30,"value":1}
+{"time":200,"device":"event0","event":"input","type":1,"code":30,"value":0}
+{"time":300,"device":"event0","event":"input","type":1,"code":48,"value":1}
+{"time":700,"device":"event0","event":"input","type":1,"code":46,"value":1}
+{"time":800,"device":"event1","event":"input","type":1,"code":304,"value":1}
+{"time":1000,"device":"event2","event":"input","type":3,"code":0,"value":500}
+{"time":1010,"device":"event2","event":"input","type":3,"code":0,"value":505}
+{"time":1020,"device":"event2","event":"input","type":3,"code":0,"value":520}
+{"time":1030,"device":"event2","event":"input","type":3,"code":0,"value":540}
+{"time":1040,"device":"event2","event":"input","type":3,"code":0,"value":560}
+{"time":1100,"device":"event0","event":"removed"}
+{"time":1200,"device":"event0","event":"input","type":1,"code":32,"value":1}
+"#,
+    )
+    .unwrap();
+    let start = 1_000_000;
+    let (broadcast_tx, mut broadcast_rx) = broadcast::channel(100);
+    let activity = Arc::new(Mutex::new(SeatActivity::new(start)));
+    run_input_listener(
+        ReplaySource::new(trace, start, false),
+        DeviceFilter::default(),
+        NoiseConfig::default(),
+        broadcast_tx,
+        activity.clone(),
+    )
+    .await
+    .unwrap();
+
+    // The key presses within the cooldown and the small touchpad movements are not sent
+    let mut messages = Vec::new();
+    while let Ok((seat, message)) = broadcast_rx.try_recv() {
+        messages.push((seat, message.seq, message.last_input));
+    }
+    let seat = |seat: &str, seq, time| (seat.to_string(), seq, start + time);
+    assert_eq!(
+        messages,
+        vec![
+            seat("seat0", 1, 100),
+            seat("seat1", 1, 150),
+            seat("seat0", 2, 700),
+            seat("seat0", 3, 1040)
+        ]
+    );
+    let mut activity = activity.lock().unwrap();
+    let seat0 = *activity.get_mut("seat0");
+    assert_eq!(seat0.keystrokes, 3);
+    assert_eq!(seat0.keyboard, start + 700);
+    assert_eq!(seat0.pointer, start + 1040);
+    assert_eq!(activity.get_mut("seat1").keystrokes, 1);
+}
+
+#[test]
+fn cooldown_test() {
+    let mut device = TrackedDevice {
+        seat: blink_timer::input::devices::DEFAULT_SEAT.to_string(),
+        direct: false,
+        noise_gate: None,
+        last_emit: Default::default(),
+    };
+    let mut activity = ActivityMessage::any(0);
+    let key = InputEvent::new(evdev::EventType::KEY.0, evdev::KeyCode::KEY_A.code(), 1);
+    assert!(device.handle(&key, 10_000, 0, &mut activity).is_some());
+    assert!(device.handle(&key, 10_100, 100, &mut activity).is_none());
+    // The clock is set back, the cooldown still ends on time
+    let message = device.handle(&key, 5_000, 600, &mut activity).unwrap();
+    assert_eq!(message.keyboard, 5_000);
+    assert_eq!(message.keystrokes, 3);
+}
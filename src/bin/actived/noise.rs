@@ -0,0 +1,310 @@
+use blink_timer::input::devices::{AbsAxis, Capability, DeviceInfo};
+use evdev::{AbsoluteAxisCode, EventType, InputEvent, KeyCode};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+
+/// Thresholds for the events of absolute axes to count as deliberate input
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoiseFilter {
+    /// Minimum movement on an axis, in percent of its range
+    pub min_delta: f64,
+    /// Minimum number of movements within the window
+    pub min_events: usize,
+    /// Window in which the movements are counted, in milliseconds
+    pub window_ms: u64,
+}
+
+impl Default for NoiseFilter {
+    fn default() -> Self {
+        Self {
+            min_delta: 1.0,
+            min_events: 3,
+            window_ms: 500,
+        }
+    }
+}
+
+/// Noise filters per device class, devices with only keys and relative axes are not filtered
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoiseConfig {
+    pub tablet: NoiseFilter,
+    pub touchpad: NoiseFilter,
+    pub touchscreen: NoiseFilter,
+    pub joystick: NoiseFilter,
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        Self {
+            tablet: NoiseFilter::default(),
+            touchpad: NoiseFilter::default(),
+            touchscreen: NoiseFilter::default(),
+            // Sticks rarely rest exactly at their center
+            joystick: NoiseFilter {
+                min_delta: 5.0,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl NoiseConfig {
+    pub fn for_device(&self, info: &DeviceInfo) -> Option<&NoiseFilter> {
+        info.capabilities
+            .iter()
+            .find_map(|capability| match capability {
+                Capability::Tablet => Some(&self.tablet),
+                Capability::Touchpad => Some(&self.touchpad),
+                Capability::Touchscreen => Some(&self.touchscreen),
+                Capability::Joystick => Some(&self.joystick),
+                _ => None,
+            })
+    }
+}
+
+/// Axes of which the movements count, the pressure, distance, size and tilt of a resting contact
+/// change without it being moved
+const POSITION_AXES: [AbsoluteAxisCode; 4] = [
+    AbsoluteAxisCode::ABS_X,
+    AbsoluteAxisCode::ABS_Y,
+    AbsoluteAxisCode::ABS_MT_POSITION_X,
+    AbsoluteAxisCode::ABS_MT_POSITION_Y,
+];
+
+struct Axis {
+    /// Minimum change of the value in device units
+    min_delta: i32,
+    /// Value at the last deliberate movement per multitouch slot, other axes only use slot 0
+    last: HashMap<i32, i32>,
+}
+
+/// Drops the noise of a device with absolute axes, like a resting palm or a jittery sensor
+pub struct NoiseGate {
+    axes: HashMap<u16, Axis>,
+    /// Multitouch slot that the following multitouch events belong to
+    slot: i32,
+    min_events: usize,
+    window_ms: u64,
+    /// Times of the movements within the window
+    movements: VecDeque<u64>,
+}
+
+impl NoiseGate {
+    pub fn new(axes: &[AbsAxis], filter: &NoiseFilter) -> Self {
+        let axes = axes
+            .iter()
+            .filter(|info| POSITION_AXES.contains(&AbsoluteAxisCode(info.code)))
+            .map(|info| {
+                let range = (info.maximum as f64 - info.minimum as f64).abs();
+                let min_delta = (range * filter.min_delta / 100.0).round() as i32;
+                // Changes within the fuzz are noise according to the driver itself
+                // A range smaller than 100 units would allow movements of zero
+                let axis = Axis {
+                    min_delta: min_delta.max(info.fuzz).max(1),
+                    last: HashMap::new(),
+                };
+                (info.code, axis)
+            })
+            .collect();
+        Self {
+            axes,
+            slot: 0,
+            min_events: filter.min_events,
+            window_ms: filter.window_ms,
+            movements: VecDeque::new(),
+        }
+    }
+
+    /// Whether the event at the given monotonic time (in ms) is deliberate input
+    pub fn accept(&mut self, event: &InputEvent, time: u64) -> bool {
+        match event.event_type() {
+            EventType::ABSOLUTE if event.code() == AbsoluteAxisCode::ABS_MT_SLOT.0 => {
+                self.slot = event.value();
+                false
+            }
+            EventType::ABSOLUTE if event.code() == AbsoluteAxisCode::ABS_MT_TRACKING_ID.0 => {
+                // A contact starts or ends in the current slot
+                for axis in self.axes.values_mut() {
+                    axis.last.remove(&self.slot);
+                }
+                false
+            }
+            EventType::ABSOLUTE => {
+                let slot = if event.code() >= AbsoluteAxisCode::ABS_MT_SLOT.0 {
+                    self.slot
+                } else {
+                    0
+                };
+                let Some(axis) = self.axes.get_mut(&event.code()) else {
+                    return false;
+                };
+                // The first value after a touch is only the start position
+                let last = axis.last.get(&slot).copied();
+                let moved = last.is_some_and(|last| (event.value() - last).abs() >= axis.min_delta);
+                if last.is_none() || moved {
+                    axis.last.insert(slot, event.value());
+                }
+                if !moved {
+                    return false;
+                }
+                self.movements.push_back(time);
+                while self
+                    .movements
+                    .front()
+                    .is_some_and(|start| time.saturating_sub(*start) > self.window_ms)
+                {
+                    self.movements.pop_front();
+                }
+                self.movements.len() >= self.min_events
+            }
+            EventType::KEY if event.code() == KeyCode::BTN_TOUCH.code() => {
+                if event.value() == 0 {
+                    // The next touch starts at a new position
+                    for axis in self.axes.values_mut() {
+                        axis.last.clear();
+                    }
+                }
+                false
+            }
+            // Touching the device without moving is not deliberate input, but its buttons are
+            EventType::KEY => !is_contact(event.code()),
+            // SYN and MSC events are sent along with the other events
+            _ => false,
+        }
+    }
+}
+
+/// Whether the key reports the contact of a finger or tool instead of a button press
+fn is_contact(code: u16) -> bool {
+    let is_tool = (KeyCode::BTN_TOOL_PEN.code()..=KeyCode::BTN_TOOL_QUADTAP.code()).contains(&code);
+    // The stylus buttons are in between the tools
+    is_tool && code != KeyCode::BTN_STYLUS.code() && code != KeyCode::BTN_STYLUS2.code()
+}
+
+#[test]
+fn contact_test() {
+    assert!(is_contact(KeyCode::BTN_TOUCH.code()));
+    assert!(is_contact(KeyCode::BTN_TOOL_FINGER.code()));
+    assert!(is_contact(KeyCode::BTN_TOOL_QUADTAP.code()));
+    assert!(!is_contact(KeyCode::BTN_STYLUS.code()));
+    assert!(!is_contact(KeyCode::BTN_LEFT.code()));
+}
+
+#[cfg(test)]
+fn test_gate(codes: &[AbsoluteAxisCode]) -> NoiseGate {
+    let axes: Vec<AbsAxis> = codes
+        .iter()
+        .map(|code| AbsAxis {
+            code: code.0,
+            minimum: 0,
+            maximum: 1000,
+            fuzz: 0,
+        })
+        .collect();
+    // Movements of at least 10 units, 3 times within 500 ms
+    NoiseGate::new(&axes, &NoiseFilter::default())
+}
+
+#[cfg(test)]
+fn abs(code: AbsoluteAxisCode, value: i32) -> InputEvent {
+    InputEvent::new(EventType::ABSOLUTE.0, code.0, value)
+}
+
+#[test]
+fn threshold_test() {
+    let mut gate = test_gate(&[AbsoluteAxisCode::ABS_X]);
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 500), 0));
+    // Jitter below the minimum delta
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 505), 10));
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 495), 20));
+    // Movements only count once there are enough of them
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 510), 30));
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 520), 40));
+    assert!(gate.accept(&abs(AbsoluteAxisCode::ABS_X, 530), 50));
+
+    // A tiny range still needs a movement
+    let axis = AbsAxis {
+        code: AbsoluteAxisCode::ABS_X.0,
+        minimum: 0,
+        maximum: 10,
+        fuzz: 0,
+    };
+    let filter = NoiseFilter {
+        min_events: 1,
+        ..Default::default()
+    };
+    let mut gate = NoiseGate::new(&[axis], &filter);
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 5), 0));
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 5), 10));
+    assert!(gate.accept(&abs(AbsoluteAxisCode::ABS_X, 6), 20));
+}
+
+#[test]
+fn window_test() {
+    let mut gate = test_gate(&[AbsoluteAxisCode::ABS_X]);
+    gate.accept(&abs(AbsoluteAxisCode::ABS_X, 0), 0);
+    // Movements that are too far apart
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 100), 0));
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 200), 400));
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 300), 1000));
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 400), 1400));
+    assert!(gate.accept(&abs(AbsoluteAxisCode::ABS_X, 500), 1450));
+}
+
+#[test]
+fn multitouch_test() {
+    let mut gate = test_gate(&[
+        AbsoluteAxisCode::ABS_MT_SLOT,
+        AbsoluteAxisCode::ABS_MT_TRACKING_ID,
+        AbsoluteAxisCode::ABS_MT_POSITION_X,
+    ]);
+    let touch = |value| InputEvent::new(EventType::KEY.0, KeyCode::BTN_TOUCH.code(), value);
+    let slot = |slot| abs(AbsoluteAxisCode::ABS_MT_SLOT, slot);
+    let x = |value| abs(AbsoluteAxisCode::ABS_MT_POSITION_X, value);
+
+    // Two resting fingers far apart are not movements
+    assert!(!gate.accept(&touch(1), 0));
+    for time in 0..5 {
+        assert!(!gate.accept(&slot(0), time * 10));
+        assert!(!gate.accept(&x(100), time * 10));
+        assert!(!gate.accept(&slot(1), time * 10));
+        assert!(!gate.accept(&x(900), time * 10));
+    }
+
+    // A new touch starts at its own position
+    assert!(!gate.accept(&touch(0), 100));
+    assert!(!gate.accept(&touch(1), 200));
+    assert!(!gate.accept(&slot(0), 200));
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_MT_TRACKING_ID, 7), 200));
+    assert!(!gate.accept(&x(500), 200));
+    assert!(!gate.accept(&x(520), 210));
+    assert!(!gate.accept(&x(540), 220));
+    assert!(gate.accept(&x(560), 230));
+}
+
+#[test]
+fn position_test() {
+    let mut gate = test_gate(&[
+        AbsoluteAxisCode::ABS_X,
+        AbsoluteAxisCode::ABS_PRESSURE,
+        AbsoluteAxisCode::ABS_MT_TOUCH_MAJOR,
+        AbsoluteAxisCode::ABS_TILT_X,
+    ]);
+    // A resting palm only changes its pressure and size
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_X, 500), 0));
+    for time in 0..10 {
+        let pressure = if time % 2 == 0 { 100 } else { 120 };
+        assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_PRESSURE, pressure), time * 10));
+        assert!(!gate.accept(
+            &abs(AbsoluteAxisCode::ABS_MT_TOUCH_MAJOR, pressure),
+            time * 10
+        ));
+        assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_TILT_X, pressure), time * 10));
+    }
+    // Axes that the device did not report are ignored as well
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_DISTANCE, 0), 100));
+    assert!(!gate.accept(&abs(AbsoluteAxisCode::ABS_DISTANCE, 500), 110));
+    assert!(gate.movements.is_empty());
+}
@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use blink_timer::input::{
+    devices::DeviceInfo,
+    source::{InputSource, SourceEvent},
+};
+use evdev::InputEvent;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// A single line in a trace file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// Time since the start of the trace, in ms
+    pub time: u64,
+    pub device: PathBuf,
+    #[serde(flatten)]
+    pub event: TraceEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    Added {
+        info: DeviceInfo,
+    },
+    Removed,
+    Input {
+        #[serde(rename = "type")]
+        event_type: u16,
+        code: u16,
+        value: i32,
+    },
+}
+
+/// Parses a trace, stored as JSON lines
+pub fn parse_trace(str: &str) -> Result<Vec<TraceEntry>> {
+    str.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("invalid trace entry on line {}", i + 1))
+        })
+        .collect()
+}
+
+pub fn read_trace(path: &Path) -> Result<Vec<TraceEntry>> {
+    let trace_str = fs::read_to_string(path).context("failed to read trace file")?;
+    parse_trace(&trace_str)
+}
+
+/// Replays the devices and input of a trace
+pub struct ReplaySource {
+    entries: VecDeque<TraceEntry>,
+    tracked: HashSet<PathBuf>,
+    /// Unix time in ms at which the trace starts
+    start: u64,
+    /// Set when the entries are replayed at their time instead of all at once
+    started_at: Option<Instant>,
+}
+
+impl ReplaySource {
+    pub fn new(entries: Vec<TraceEntry>, start: u64, realtime: bool) -> Self {
+        Self {
+            entries: entries.into(),
+            tracked: HashSet::new(),
+            start,
+            started_at: realtime.then(Instant::now),
+        }
+    }
+}
+
+impl InputSource for ReplaySource {
+    async fn next(&mut self) -> Result<Option<SourceEvent>> {
+        while let Some(entry) = self.entries.pop_front() {
+            if let Some(started_at) = self.started_at {
+                tokio::time::sleep_until(started_at + Duration::from_millis(entry.time)).await;
+            }
+            let path = entry.device;
+            match entry.event {
+                TraceEvent::Added { info } => return Ok(Some(SourceEvent::Added { path, info })),
+                TraceEvent::Removed => {
+                    self.tracked.remove(&path);
+                    return Ok(Some(SourceEvent::Removed { path }));
+                }
+                // Like a real device, the input is only read once it is tracked
+                TraceEvent::Input { .. } if !self.tracked.contains(&path) => {}
+                TraceEvent::Input {
+                    event_type,
+                    code,
+                    value,
+                } => {
+                    return Ok(Some(SourceEvent::Input {
+                        path,
+                        event: InputEvent::new(event_type, code, value),
+                        time: self.start + entry.time,
+                        uptime: entry.time,
+                    }));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn track(&mut self, path: &Path) -> Result<()> {
+        self.tracked.insert(path.to_path_buf());
+        Ok(())
+    }
+}
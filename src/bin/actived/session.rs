@@ -0,0 +1,168 @@
+use blink_timer::input::devices::DEFAULT_SEAT;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use zbus::zvariant::OwnedObjectPath;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const LOGIND: &str = "org.freedesktop.login1";
+
+/// Decides which clients can receive the activity of a seat, based on their uid
+#[derive(Clone)]
+pub struct Access {
+    allowed_uids: Arc<Vec<u32>>,
+    /// Users of the active sessions per seat, updated from systemd-logind
+    active_uids: Arc<Mutex<HashMap<String, u32>>>,
+    conn: Option<zbus::Connection>,
+}
+
+impl Access {
+    pub async fn new(allowed_uids: Vec<u32>) -> Self {
+        let conn = match zbus::Connection::system().await {
+            Ok(conn) => Some(conn),
+            Err(e) => {
+                log::warn!(
+                    "Failed to connect to the system bus, only root and the allowed users can connect and all clients get the activity of {DEFAULT_SEAT}: {e}"
+                );
+                None
+            }
+        };
+        let access = Self {
+            allowed_uids: Arc::new(allowed_uids),
+            active_uids: Arc::new(Mutex::new(HashMap::new())),
+            conn,
+        };
+        // Users of the active sessions can connect as soon as clients are accepted
+        access.poll_active_sessions().await;
+        access
+    }
+
+    pub fn is_allowed(&self, uid: u32, seat: &str) -> bool {
+        uid == 0
+            || self.allowed_uids.contains(&uid)
+            || self.active_uids.lock().unwrap().get(seat) == Some(&uid)
+    }
+
+    /// Seat of the session the client runs in, a client outside of any session gets the seat of
+    /// the graphical session of its user
+    pub async fn seat_of(&self, pid: Option<u32>, uid: u32) -> String {
+        let Some(conn) = &self.conn else {
+            return DEFAULT_SEAT.to_string();
+        };
+        match client_seat(conn, pid, uid).await {
+            Ok(Some(seat)) => seat,
+            Ok(None) => DEFAULT_SEAT.to_string(),
+            Err(e) => {
+                log::debug!("Failed to get the seat of uid {uid} from logind: {e}");
+                DEFAULT_SEAT.to_string()
+            }
+        }
+    }
+
+    /// Keeps the users of the active sessions up to date, such that access moves along when
+    /// switching users
+    pub async fn watch_active_sessions(self) {
+        if self.conn.is_none() {
+            return;
+        }
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            self.poll_active_sessions().await;
+        }
+    }
+
+    /// Queries the active sessions, the last known sessions are kept when logind fails to respond
+    async fn poll_active_sessions(&self) {
+        let Some(conn) = &self.conn else {
+            return;
+        };
+        match active_uids(conn).await {
+            Ok(uids) => self.update(uids),
+            Err(e) => log::debug!("Failed to get the active sessions from logind: {e}"),
+        }
+    }
+
+    fn update(&self, uids: HashMap<String, u32>) {
+        let mut active_uids = self.active_uids.lock().unwrap();
+        let seats: BTreeSet<&String> = uids.keys().chain(active_uids.keys()).collect();
+        for seat in seats {
+            if uids.get(seat) == active_uids.get(seat) {
+                continue;
+            }
+            match uids.get(seat) {
+                Some(uid) => log::info!("Active session on {seat} belongs to uid {uid}"),
+                None => log::info!("No active session on {seat}"),
+            }
+        }
+        *active_uids = uids;
+    }
+}
+
+async fn manager(conn: &zbus::Connection) -> zbus::Result<zbus::Proxy<'static>> {
+    zbus::Proxy::new(
+        conn,
+        LOGIND,
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )
+    .await
+}
+
+/// Users of the active sessions, by the id of their seat
+async fn active_uids(conn: &zbus::Connection) -> zbus::Result<HashMap<String, u32>> {
+    let seats: Vec<(String, OwnedObjectPath)> = manager(conn).await?.call("ListSeats", &()).await?;
+    let mut uids = HashMap::new();
+    for (id, path) in seats {
+        if let Some(uid) = active_uid(conn, path).await? {
+            uids.insert(id, uid);
+        }
+    }
+    Ok(uids)
+}
+
+async fn active_uid(conn: &zbus::Connection, seat: OwnedObjectPath) -> zbus::Result<Option<u32>> {
+    let seat = zbus::Proxy::new(conn, LOGIND, seat, "org.freedesktop.login1.Seat").await?;
+    let (id, path): (String, OwnedObjectPath) = seat.get_property("ActiveSession").await?;
+    if id.is_empty() {
+        return Ok(None);
+    }
+    let session = zbus::Proxy::new(conn, LOGIND, path, "org.freedesktop.login1.Session").await?;
+    let (uid, _): (u32, OwnedObjectPath) = session.get_property("User").await?;
+    Ok(Some(uid))
+}
+
+async fn client_seat(
+    conn: &zbus::Connection,
+    pid: Option<u32>,
+    uid: u32,
+) -> zbus::Result<Option<String>> {
+    let manager = manager(conn).await?;
+    if let Some(pid) = pid {
+        // Fails when the client is not part of a session
+        let session: zbus::Result<OwnedObjectPath> = manager.call("GetSessionByPID", &(pid,)).await;
+        if let Ok(session) = session
+            && let Some(seat) = session_seat(conn, session).await?
+        {
+            return Ok(Some(seat));
+        }
+    }
+    // Services of the user, like blinkd, run outside of the sessions of the user
+    let user: OwnedObjectPath = manager.call("GetUser", &(uid,)).await?;
+    let user = zbus::Proxy::new(conn, LOGIND, user, "org.freedesktop.login1.User").await?;
+    let (id, session): (String, OwnedObjectPath) = user.get_property("Display").await?;
+    if id.is_empty() {
+        return Ok(None);
+    }
+    session_seat(conn, session).await
+}
+
+async fn session_seat(
+    conn: &zbus::Connection,
+    session: OwnedObjectPath,
+) -> zbus::Result<Option<String>> {
+    let session = zbus::Proxy::new(conn, LOGIND, session, "org.freedesktop.login1.Session").await?;
+    let (id, _): (String, OwnedObjectPath) = session.get_property("Seat").await?;
+    Ok((!id.is_empty()).then_some(id))
+}
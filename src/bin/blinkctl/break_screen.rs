@@ -0,0 +1,113 @@
+use anyhow::Result;
+use blink_timer::{BreakInfo, DurationExt};
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+use tokio::signal::unix::{SignalKind, signal};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Switches to the alternate screen of the terminal until it is dropped
+struct AlternateScreen;
+
+impl AlternateScreen {
+    fn enter() -> io::Result<Self> {
+        // Hide the cursor as well
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Shows a countdown of the break, returns how long the break lasted and whether it was completed
+pub async fn run(info: &BreakInfo) -> Result<(Duration, bool)> {
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    // Closing the terminal window aborts the break as well
+    let mut sighup = signal(SignalKind::hangup())?;
+    let mut interval = tokio::time::interval(REDRAW_INTERVAL);
+
+    let screen = AlternateScreen::enter()?;
+    let start = Instant::now();
+    let completed = loop {
+        let Some(remaining) = info.duration.checked_sub(start.elapsed()) else {
+            break true;
+        };
+        draw(info, remaining)?;
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = sigint.recv() => break false,
+            _ = sigterm.recv() => break false,
+            _ = sighup.recv() => break false,
+        }
+    };
+    drop(screen);
+    Ok((start.elapsed().min(info.duration), completed))
+}
+
+fn draw(info: &BreakInfo, remaining: Duration) -> io::Result<()> {
+    // Round up, such that the countdown ends at 00:00 instead of starting one second short
+    let remaining = Duration::from_secs(remaining.as_secs() + 1).min(info.duration);
+    let mut lines = vec![
+        format!("\x1b[1m{}\x1b[0m", info.name),
+        String::new(),
+        format!("\x1b[1m{}\x1b[0m", remaining.display()),
+    ];
+    if let Some(description) = &info.description {
+        lines.push(String::new());
+        lines.push(description.clone());
+    }
+    lines.push(String::new());
+    lines.push("Press Ctrl+C to abort the break".to_string());
+
+    let (width, height) = terminal_size();
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "\x1b[2J")?;
+    let top = height.saturating_sub(lines.len()) / 2;
+    for (row, line) in lines.iter().enumerate() {
+        let visible_len = strip_escapes(line).chars().count();
+        let column = width.saturating_sub(visible_len) / 2;
+        write!(stdout, "\x1b[{};{}H{line}", top + row + 1, column + 1)?;
+    }
+    stdout.flush()
+}
+
+/// Removes the escape sequences used for styling to get the visible text
+fn strip_escapes(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut in_escape = false;
+    for c in line.chars() {
+        match c {
+            '\x1b' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if !in_escape => result.push(c),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Size of the terminal in columns and rows, with a fallback if it is unknown
+fn terminal_size() -> (usize, usize) {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes to the given winsize struct
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result == 0 && size.ws_col > 0 && size.ws_row > 0 {
+        (size.ws_col as usize, size.ws_row as usize)
+    } else {
+        (80, 24)
+    }
+}
@@ -0,0 +1,278 @@
+use anyhow::{Context, Result, bail};
+use blink_timer::{
+    DurationExt, IpcRequest, IpcResponse, TimerAction,
+    async_socket::SocketStream,
+    config::parse_duration,
+    history::{self, History},
+};
+use clap::Parser;
+use jiff::{
+    Zoned,
+    civil::{Date, Time},
+    tz::TimeZone,
+};
+use std::time::Duration;
+
+mod break_screen;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .init();
+    let args = Args::parse();
+    match args.cmd {
+        // Statistics are read from the history file, so the daemon does not need to run
+        Command::Stats { since } => print_stats(since)?,
+        Command::Daemon(cmd) => Client::connect().await?.run(cmd).await?,
+    }
+    Ok(())
+}
+
+fn print_stats(since: Option<Date>) -> Result<()> {
+    let path = blink_timer::history_path().context("No data directory available")?;
+    let entries = History::new(path).load()?;
+    let stats = history::daily_stats(&entries, &TimeZone::system());
+    println!(
+        "{:<10}  {:>7}  {:>6}  {:>14}  {:>11}",
+        "Date", "Prompts", "Breaks", "Longest screen", "Active time"
+    );
+    for (date, day) in stats
+        .iter()
+        .filter(|(date, _)| since.is_none_or(|since| **date >= since))
+    {
+        println!(
+            "{:<10}  {:>7}  {:>6}  {:>14}  {:>11}",
+            date.to_string(),
+            day.prompts,
+            day.breaks,
+            day.longest_session.display().to_string(),
+            day.active.display().to_string()
+        );
+    }
+    Ok(())
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    #[command(subcommand)]
+    pub cmd: Command,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Command {
+    #[command(flatten)]
+    Daemon(DaemonCommand),
+    /// Show statistics of the break history per day
+    Stats {
+        /// Only show days since a date, 'yyyy-mm-dd'
+        #[arg(long)]
+        since: Option<Date>,
+    },
+}
+
+/// Commands that are sent to the daemon
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum DaemonCommand {
+    /// Get status of current timers
+    Status {
+        /// Print the status as JSON
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
+        /// Print the status using a template with placeholders like '{remaining}', see the README
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Toggle the timer
+    Toggle,
+    /// Reset all timers
+    Reset,
+    /// Postpone the due break or the next timer
+    Snooze {
+        /// Minimum time until the next notification, 'mm:ss' or 'hh:mm:ss'
+        #[arg(value_parser = parse_duration)]
+        duration: Duration,
+    },
+    /// Disable the timers for some time, after which they are enabled again
+    Pause {
+        /// Duration of the pause, 'mm:ss' or 'hh:mm:ss'
+        #[arg(value_parser = parse_duration, required_unless_present = "until")]
+        duration: Option<Duration>,
+        /// Pause until a local time of day, 'hh:mm'
+        #[arg(long, conflicts_with = "duration")]
+        until: Option<Time>,
+    },
+    /// Reload the config file
+    Reload,
+    /// Take a break with a countdown in the terminal
+    Break {
+        /// Id of the timer, defaults to the current break or the next timer
+        #[arg(long)]
+        timer: Option<String>,
+        /// Length of the break, defaults to the break duration of the timer
+        #[arg(long, value_parser = parse_duration)]
+        duration: Option<Duration>,
+    },
+    /// Print the status and events of the daemon as they happen
+    Watch {
+        /// Print the events as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+    /// List all timers
+    Timers,
+    /// Control a single timer
+    Timer {
+        #[command(subcommand)]
+        cmd: TimerCommand,
+    },
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum TimerCommand {
+    /// Enable a timer
+    Enable { id: String },
+    /// Disable a timer until it is enabled again
+    Disable { id: String },
+    /// Reset a timer
+    Reset { id: String },
+}
+
+impl TimerCommand {
+    fn into_request(self) -> IpcRequest {
+        match self {
+            TimerCommand::Enable { id } => IpcRequest::Timer(id, TimerAction::Enable),
+            TimerCommand::Disable { id } => IpcRequest::Timer(id, TimerAction::Disable),
+            TimerCommand::Reset { id } => IpcRequest::Timer(id, TimerAction::Reset),
+        }
+    }
+}
+
+/// Returns the duration until the next occurrence of a local time of day
+fn duration_until(time: Time) -> Result<Duration> {
+    let now = Zoned::now();
+    let mut target = now.with().time(time).build()?;
+    if target <= now {
+        target = target.tomorrow()?;
+    }
+    Duration::try_from(now.duration_until(&target)).context("invalid pause duration")
+}
+
+struct Client {
+    stream: SocketStream,
+}
+
+impl Client {
+    async fn connect() -> Result<Self> {
+        let stream = SocketStream::connect(blink_timer::socket_path()).await?;
+        Ok(Self { stream })
+    }
+
+    async fn run(&mut self, cmd: DaemonCommand) -> Result<()> {
+        match cmd {
+            DaemonCommand::Status { json, format } => {
+                if let IpcResponse::Status(status) =
+                    self.stream.send_and_recv(IpcRequest::Status).await?
+                {
+                    if json {
+                        println!("{}", status.to_json());
+                    } else if let Some(template) = format {
+                        println!("{}", status.format(&template));
+                    } else {
+                        println!("{status}");
+                    }
+                }
+            }
+            DaemonCommand::Toggle => self.send_command(IpcRequest::Toggle).await?,
+            DaemonCommand::Reset => self.send_command(IpcRequest::Reset).await?,
+            DaemonCommand::Snooze { duration } => {
+                self.send_command(IpcRequest::Snooze(duration)).await?
+            }
+            DaemonCommand::Pause { duration, until } => {
+                let duration = match until {
+                    Some(time) => duration_until(time)?,
+                    None => duration.context("no pause duration given")?,
+                };
+                self.send_command(IpcRequest::Pause(duration)).await?
+            }
+            DaemonCommand::Reload => self.send_command(IpcRequest::Reload).await?,
+            DaemonCommand::Break { timer, duration } => {
+                let request = IpcRequest::BeginBreak {
+                    id: timer,
+                    duration,
+                };
+                let info = match self.stream.send_and_recv(request).await? {
+                    IpcResponse::Break(info) => info,
+                    IpcResponse::Error(e) => bail!(e),
+                    _ => bail!("Unexpected response from daemon"),
+                };
+                let (duration, completed) = break_screen::run(&info).await?;
+                // The daemon handles a single request per connection
+                *self = Client::connect().await?;
+                self.send_command(IpcRequest::EndBreak {
+                    id: info.id,
+                    duration,
+                    completed,
+                })
+                .await?;
+                if completed {
+                    println!("Break completed");
+                } else {
+                    println!("Break aborted after {}", duration.display());
+                }
+            }
+            DaemonCommand::Watch { json } => {
+                self.stream.send(IpcRequest::Subscribe).await?;
+                loop {
+                    match self.stream.recv().await? {
+                        IpcResponse::Event(event) if json => println!("{}", event.to_json()),
+                        IpcResponse::Event(event) => println!("{event}"),
+                        IpcResponse::Error(e) => bail!(e),
+                        _ => eprintln!("Unexpected response from daemon"),
+                    }
+                }
+            }
+            DaemonCommand::Timers => {
+                if let IpcResponse::Timers(timers) =
+                    self.stream.send_and_recv(IpcRequest::ListTimers).await?
+                {
+                    println!(
+                        "{:<16}  {:<20}  {:>9}  {:>7}  State",
+                        "Id", "Name", "Time left", "Prompts"
+                    );
+                    for timer in timers {
+                        let state = match (timer.is_enabled, timer.is_active, timer.is_next) {
+                            (false, _, _) => "disabled",
+                            (true, false, _) => "inactive",
+                            (true, true, true) => "next",
+                            (true, true, false) => "enabled",
+                        };
+                        // Typing timers show their progress instead of the time left
+                        let left = match &timer.typing {
+                            Some(typing) => typing.to_string(),
+                            None => timer.time_left.display().to_string(),
+                        };
+                        println!(
+                            "{:<16}  {:<20}  {:>9}  {:>7}  {state}",
+                            timer.id, timer.name, left, timer.prompts
+                        );
+                    }
+                }
+            }
+            DaemonCommand::Timer { cmd } => self.send_command(cmd.into_request()).await?,
+        };
+        Ok(())
+    }
+
+    /// Sends a request that is expected to respond with `IpcResponse::Ok`
+    async fn send_command(&mut self, request: IpcRequest) -> Result<()> {
+        match self.stream.send_and_recv(request).await? {
+            IpcResponse::Ok => {}
+            IpcResponse::Error(e) => bail!(e),
+            _ => eprintln!("Unexpected response from daemon"),
+        }
+        Ok(())
+    }
+}
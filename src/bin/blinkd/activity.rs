@@ -0,0 +1,527 @@
+use anyhow::{Context, Result, bail};
+use blink_timer::{
+    ACTIVED_PROTOCOL_VERSION, ActivedHello, ActivityMessage, DurationExt, InputKind,
+    actived_features,
+    async_socket::SocketStream,
+    config::ActivitySource,
+    get_unix_time_ms,
+    input::{
+        devices::DeviceFilter,
+        source::{EvdevSource, InputSource, SourceEvent},
+    },
+};
+use std::{
+    collections::HashMap,
+    ffi::{CStr, c_char, c_int, c_ulong, c_void},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::mpsc, task::JoinSet, time::Instant};
+
+// Interval at which the sources without events are polled
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Bounds of the delay between attempts to connect to the activity source
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+pub enum ActivityUpdate {
+    Connected {
+        has_input_kinds: bool,
+        has_input_counts: bool,
+    },
+    Activity(ActivityMessage),
+    Disconnected,
+}
+
+/// Connection with the activity source that is reestablished with exponential backoff
+pub struct ActivityConnection {
+    source: ActivitySource,
+    /// Socket of actived, when it is the source
+    actived_path: PathBuf,
+    stream: Option<ActivityStream>,
+    /// Delay before the next connection attempt, only reset once the source sent an update
+    backoff: Duration,
+    /// Time of the next connection attempt, immediately if not set
+    retry_at: Option<Instant>,
+}
+
+impl ActivityConnection {
+    pub fn new(source: ActivitySource) -> Self {
+        Self {
+            source,
+            actived_path: blink_timer::actived_socket_path(),
+            stream: None,
+            backoff: MIN_BACKOFF,
+            retry_at: None,
+        }
+    }
+
+    /// Waits for the next update, this is cancel safe between connection attempts
+    pub async fn recv(&mut self) -> ActivityUpdate {
+        loop {
+            if let Some(stream) = &mut self.stream {
+                match stream.recv().await {
+                    Ok(activity) => {
+                        // A source that accepts connections but fails right away is retried with
+                        // the backoff, so it is only reset once the source works
+                        self.backoff = MIN_BACKOFF;
+                        return ActivityUpdate::Activity(activity);
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "Activity source failed, reconnecting in {}: {e:?}",
+                            self.backoff.display()
+                        );
+                        self.stream = None;
+                        self.retry_at = Some(Instant::now() + self.backoff);
+                        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                        return ActivityUpdate::Disconnected;
+                    }
+                }
+            }
+
+            if let Some(retry_at) = self.retry_at {
+                tokio::time::sleep_until(retry_at).await;
+            }
+            match ActivityStream::connect(self.source, &self.actived_path).await {
+                Ok(stream) => {
+                    self.retry_at = None;
+                    let update = ActivityUpdate::Connected {
+                        has_input_kinds: stream.has_input_kinds(),
+                        has_input_counts: stream.has_input_counts(),
+                    };
+                    self.stream = Some(stream);
+                    return update;
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Failed to connect to {:?}, retrying in {}: {e:#}",
+                        self.source,
+                        self.backoff.display()
+                    );
+                    self.retry_at = Some(Instant::now() + self.backoff);
+                    self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+}
+
+/// Reports the time of the last input from one of the configured activity sources
+pub enum ActivityStream {
+    Actived {
+        stream: SocketStream,
+        /// The hello with which actived answered
+        hello: ActivedHello,
+        last_seq: Option<u64>,
+    },
+    Logind(LogindSession),
+    X11(X11Idle),
+    Evdev {
+        /// Notified when the activity was updated
+        receiver: mpsc::UnboundedReceiver<()>,
+        activity: Arc<Mutex<ActivityMessage>>,
+        /// The task that tracks the devices, which is aborted together with the listeners of the
+        /// devices when the stream is dropped
+        tracker: JoinSet<()>,
+    },
+}
+
+impl ActivityStream {
+    pub async fn connect(source: ActivitySource, actived_path: &Path) -> Result<Self> {
+        let stream = match source {
+            ActivitySource::Actived => {
+                let (stream, hello) = connect_actived(actived_path).await?;
+                Self::Actived {
+                    stream,
+                    hello,
+                    last_seq: None,
+                }
+            }
+            ActivitySource::Logind => Self::Logind(LogindSession::connect().await?),
+            ActivitySource::X11 => Self::X11(X11Idle::open()?),
+            ActivitySource::Evdev => {
+                let source = EvdevSource::new().context("failed to watch the input devices")?;
+                let activity = Arc::new(Mutex::new(ActivityMessage::any(get_unix_time_ms())));
+                let (sender, receiver) = mpsc::unbounded_channel();
+                let mut tracker = JoinSet::new();
+                tracker.spawn(track_devices(source, activity.clone(), sender));
+                Self::Evdev {
+                    receiver,
+                    activity,
+                    tracker,
+                }
+            }
+        };
+        log::info!("Tracking input with {source:?}");
+        Ok(stream)
+    }
+
+    /// Whether the source reports the kinds of input separately
+    pub fn has_input_kinds(&self) -> bool {
+        match self {
+            Self::Actived { hello, .. } => hello.has_feature(actived_features::INPUT_KINDS),
+            Self::Evdev { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the source counts the key presses and pointer movement
+    pub fn has_input_counts(&self) -> bool {
+        match self {
+            Self::Actived { hello, .. } => hello.has_feature(actived_features::INPUT_COUNTS),
+            Self::Evdev { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Waits for the next update of the last input times
+    pub async fn recv(&mut self) -> Result<ActivityMessage> {
+        match self {
+            Self::Actived {
+                stream, last_seq, ..
+            } => {
+                let activity: ActivityMessage = stream.recv().await?;
+                if let Some(last_seq) = last_seq
+                    && activity.seq > *last_seq + 1
+                {
+                    log::debug!("Missed {} activity updates", activity.seq - *last_seq - 1);
+                }
+                *last_seq = Some(activity.seq);
+                Ok(activity)
+            }
+            Self::Logind(session) => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                Ok(ActivityMessage::any(session.last_input().await?))
+            }
+            Self::X11(idle) => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                let idle = idle.query()?.as_millis() as u64;
+                Ok(ActivityMessage::any(
+                    get_unix_time_ms().saturating_sub(idle),
+                ))
+            }
+            Self::Evdev {
+                receiver,
+                activity,
+                tracker,
+            } => {
+                if receiver.recv().await.is_none() {
+                    if let Some(Err(e)) = tracker.join_next().await {
+                        bail!("stopped tracking the input devices: {e}");
+                    }
+                    bail!("stopped tracking the input devices");
+                }
+                Ok(*activity.lock().unwrap())
+            }
+        }
+    }
+}
+
+/// The graphical session of the user in systemd-logind, which has an idle hint set by the desktop
+pub struct LogindSession {
+    proxy: zbus::Proxy<'static>,
+}
+
+impl LogindSession {
+    async fn connect() -> Result<Self> {
+        let conn = zbus::Connection::system()
+            .await
+            .context("failed to connect to the system bus")?;
+        // blinkd usually runs as a user service outside of the session, so look up the display
+        // session of the user instead of the session of the process
+        let user = zbus::Proxy::new(
+            &conn,
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1/user/self",
+            "org.freedesktop.login1.User",
+        )
+        .await?;
+        let (id, path): (String, zbus::zvariant::OwnedObjectPath) = user
+            .get_property("Display")
+            .await
+            .context("failed to get the display session from logind")?;
+        if id.is_empty() {
+            bail!("the user has no graphical session in logind");
+        }
+        log::debug!("Using logind session '{id}'");
+        let proxy = zbus::Proxy::new_owned(
+            conn,
+            "org.freedesktop.login1",
+            path,
+            "org.freedesktop.login1.Session",
+        )
+        .await?;
+        Ok(Self { proxy })
+    }
+
+    /// Unix time in milliseconds of the last input
+    async fn last_input(&self) -> Result<u64> {
+        let idle: bool = self.proxy.get_property("IdleHint").await?;
+        if !idle {
+            return Ok(get_unix_time_ms());
+        }
+        // Realtime in microseconds at which the session became idle
+        let idle_since: u64 = self.proxy.get_property("IdleSinceHint").await?;
+        Ok(idle_since / 1000)
+    }
+}
+
+/// Layout of `XScreenSaverInfo` from libXss
+#[repr(C)]
+#[derive(Default)]
+struct XScreenSaverInfo {
+    window: c_ulong,
+    state: c_int,
+    kind: c_int,
+    til_or_since: c_ulong,
+    /// Milliseconds since the last input
+    idle: c_ulong,
+    event_mask: c_ulong,
+}
+
+type XOpenDisplay = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type XCloseDisplay = unsafe extern "C" fn(*mut c_void) -> c_int;
+type XDefaultRootWindow = unsafe extern "C" fn(*mut c_void) -> c_ulong;
+type XScreenSaverQueryInfo =
+    unsafe extern "C" fn(*mut c_void, c_ulong, *mut XScreenSaverInfo) -> c_int;
+
+/// Idle time from the X11 screensaver extension, the libraries are loaded at runtime such that
+/// blinkd does not depend on X11
+pub struct X11Idle {
+    display: *mut c_void,
+    root: c_ulong,
+    query_info: XScreenSaverQueryInfo,
+    close_display: XCloseDisplay,
+    // The libraries stay loaded until the display is closed, fields are dropped after `drop`
+    _xss: Library,
+    _x11: Library,
+}
+
+// SAFETY: Xlib can be called from any thread as long as the calls on a display do not overlap.
+// X11Idle owns its display and is not Sync, so the display is only used by one thread at a time.
+unsafe impl Send for X11Idle {}
+
+/// A library loaded with dlopen, which is unloaded when dropped
+struct Library(*mut c_void);
+
+impl Library {
+    fn load(name: &CStr) -> Result<Self> {
+        // SAFETY: dlopen is called with a valid C string
+        let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            bail!("failed to load {}", name.to_string_lossy());
+        }
+        Ok(Self(handle))
+    }
+
+    fn symbol(&self, name: &CStr) -> Result<*mut c_void> {
+        // SAFETY: the handle was returned by dlopen and the name is a valid C string
+        let symbol = unsafe { libc::dlsym(self.0, name.as_ptr()) };
+        if symbol.is_null() {
+            bail!("failed to load symbol {}", name.to_string_lossy());
+        }
+        Ok(symbol)
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        // SAFETY: the handle was returned by dlopen and its symbols are no longer used
+        unsafe { libc::dlclose(self.0) };
+    }
+}
+
+impl X11Idle {
+    fn open() -> Result<Self> {
+        let x11 = Library::load(c"libX11.so.6")?;
+        let xss = Library::load(c"libXss.so.1")?;
+        // SAFETY: the symbols are cast to their signatures from the Xlib and libXss headers
+        unsafe {
+            let open_display: XOpenDisplay = std::mem::transmute(x11.symbol(c"XOpenDisplay")?);
+            let close_display: XCloseDisplay = std::mem::transmute(x11.symbol(c"XCloseDisplay")?);
+            let default_root_window: XDefaultRootWindow =
+                std::mem::transmute(x11.symbol(c"XDefaultRootWindow")?);
+            let query_info: XScreenSaverQueryInfo =
+                std::mem::transmute(xss.symbol(c"XScreenSaverQueryInfo")?);
+            // Uses the DISPLAY environment variable
+            let display = open_display(std::ptr::null());
+            if display.is_null() {
+                bail!("failed to open the X11 display, is DISPLAY set?");
+            }
+            Ok(Self {
+                display,
+                root: default_root_window(display),
+                query_info,
+                close_display,
+                _xss: xss,
+                _x11: x11,
+            })
+        }
+    }
+
+    fn query(&self) -> Result<Duration> {
+        let mut info = XScreenSaverInfo::default();
+        // SAFETY: the display is open and the info struct matches the C layout
+        let status = unsafe { (self.query_info)(self.display, self.root, &mut info) };
+        if status == 0 {
+            bail!("the X11 screensaver extension is not available");
+        }
+        // c_ulong is only 32 bits on some platforms
+        #[allow(clippy::unnecessary_cast)]
+        Ok(Duration::from_millis(info.idle as u64))
+    }
+}
+
+impl Drop for X11Idle {
+    fn drop(&mut self) {
+        // SAFETY: the display is open and not used afterwards, the libraries are unloaded after it
+        unsafe { (self.close_display)(self.display) };
+    }
+}
+
+/// Connects to actived and checks that it uses the same protocol version
+async fn connect_actived(path: &Path) -> Result<(SocketStream, ActivedHello)> {
+    let mut stream = SocketStream::connect(path.to_path_buf()).await?;
+    stream.send(&ActivedHello::current()).await?;
+    let hello: ActivedHello = stream.recv().await.map_err(|e| {
+        // actived closes the connection without an answer when the user is not allowed
+        if e.downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::UnexpectedEof)
+        {
+            e.context("actived refused the connection, the user does not own the active session")
+        } else {
+            e.context(
+                "actived did not answer the hello, it is probably older than blinkd and needs to be updated",
+            )
+        }
+    })?;
+    if hello.version != ACTIVED_PROTOCOL_VERSION {
+        bail!(
+            "actived uses protocol version {} but blinkd uses version {ACTIVED_PROTOCOL_VERSION}, update both to the same release",
+            hello.version
+        );
+    }
+    log::debug!(
+        "Connected to actived (protocol version {}, features: {})",
+        hello.version,
+        hello.features.join(", ")
+    );
+    Ok((stream, hello))
+}
+
+/// Reads the input devices directly, which requires read access to `/dev/input` but no root
+/// daemon, e.g. by adding the user to the `input` group. The same kinds of devices as actived
+/// tracks by default are tracked, including the devices that are plugged in later
+async fn track_devices(
+    mut source: EvdevSource,
+    activity: Arc<Mutex<ActivityMessage>>,
+    sender: mpsc::UnboundedSender<()>,
+) {
+    let filter = DeviceFilter::default();
+    // Whether the input is on the screen itself, for each tracked device
+    let mut tracked = HashMap::new();
+    let mut last_sent = [0; InputKind::ALL.len()];
+    loop {
+        let event = match source.next().await {
+            Ok(Some(event)) => event,
+            Ok(None) => return,
+            Err(e) => {
+                log::warn!("Stopped tracking input devices: {e:#}");
+                return;
+            }
+        };
+        match event {
+            SourceEvent::Added { path, info } => {
+                if !filter.check(&info).is_tracked() {
+                    continue;
+                }
+                match source.track(&path) {
+                    Ok(()) => {
+                        log::info!(
+                            "Listening for events on '{}' ({})",
+                            info.name,
+                            path.display()
+                        );
+                        tracked.insert(path, info.direct);
+                    }
+                    Err(e) => log::warn!("Failed to listen on '{}': {e:#}", path.display()),
+                }
+            }
+            SourceEvent::Removed { path } => {
+                tracked.remove(&path);
+            }
+            SourceEvent::Scanned => {
+                if tracked.is_empty() {
+                    log::warn!("No readable input devices found, is the user in the input group?");
+                }
+            }
+            SourceEvent::Input {
+                path, event, time, ..
+            } => {
+                let Some(kind) = tracked
+                    .get(&path)
+                    .and_then(|direct| InputKind::of_event(&event, *direct))
+                else {
+                    continue;
+                };
+                let mut activity = activity.lock().unwrap();
+                activity.count(&event, kind);
+                // Only send once per second to limit the number of updates
+                if time.saturating_sub(last_sent[kind as usize]) >= 1000 {
+                    last_sent[kind as usize] = time;
+                    activity.record(kind, time);
+                    if sender.send(()).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn backoff_test() {
+    use blink_timer::async_socket::SocketServer;
+
+    let path = std::env::temp_dir()
+        .join(format!("blink-backoff-{}", std::process::id()))
+        .join("actived.sock");
+    let mut server = SocketServer::create(path.clone(), false).await.unwrap();
+    tokio::spawn(async move {
+        // The first client is disconnected right after the handshake, the second gets an update
+        for updates in [0, 1] {
+            let mut stream = server.accept_client().await.unwrap();
+            let _: ActivedHello = stream.recv().await.unwrap();
+            stream.send(&ActivedHello::current()).await.unwrap();
+            for _ in 0..updates {
+                stream.send(&ActivityMessage::any(0)).await.unwrap();
+            }
+        }
+    });
+
+    let mut connection = ActivityConnection::new(ActivitySource::Actived);
+    connection.actived_path = path.clone();
+    assert!(matches!(
+        connection.recv().await,
+        ActivityUpdate::Connected { .. }
+    ));
+    assert!(matches!(
+        connection.recv().await,
+        ActivityUpdate::Disconnected
+    ));
+    assert_eq!(connection.backoff, MIN_BACKOFF * 2);
+
+    assert!(matches!(
+        connection.recv().await,
+        ActivityUpdate::Connected { .. }
+    ));
+    assert!(matches!(
+        connection.recv().await,
+        ActivityUpdate::Activity(_)
+    ));
+    assert_eq!(connection.backoff, MIN_BACKOFF);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
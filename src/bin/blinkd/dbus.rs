@@ -0,0 +1,319 @@
+use super::Daemon;
+use anyhow::{Context, Result};
+use blink_timer::{Event, IpcRequest, IpcResponse, Status, TimerAction};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::broadcast;
+use zbus::{connection, fdo, object_server::SignalEmitter};
+
+pub const BUS_NAME: &str = "nl.rijkvp.Blink";
+pub const OBJECT_PATH: &str = "/nl/rijkvp/Blink";
+
+/// A timer as (id, name, seconds left, prompts, enabled, active, next)
+type TimerTuple = (String, String, u64, u64, bool, bool, bool);
+
+/// The `nl.rijkvp.Blink` interface, mirrors the requests of the Unix socket
+struct BlinkInterface {
+    daemon: Arc<Mutex<Daemon>>,
+}
+
+impl BlinkInterface {
+    fn request(&self, request: IpcRequest) -> fdo::Result<IpcResponse> {
+        let mut daemon = self.daemon.lock().unwrap();
+        match daemon.handle_msg(request) {
+            Ok(IpcResponse::Error(e)) => Err(fdo::Error::Failed(e)),
+            Ok(resp) => Ok(resp),
+            Err(e) => Err(fdo::Error::Failed(format!("{e:#}"))),
+        }
+    }
+
+    fn command(&self, request: IpcRequest) -> fdo::Result<()> {
+        self.request(request).map(|_| ())
+    }
+
+    fn status_value(&self) -> Status {
+        self.daemon.lock().unwrap().status()
+    }
+}
+
+#[zbus::interface(name = "nl.rijkvp.Blink")]
+impl BlinkInterface {
+    /// Status in the same format as `blinkctl status`
+    fn status(&self) -> String {
+        self.status_value().to_string()
+    }
+
+    /// Status as JSON, like `blinkctl status --json`
+    fn status_json(&self) -> String {
+        self.status_value().to_json().to_string()
+    }
+
+    fn toggle(&self) -> fdo::Result<()> {
+        self.command(IpcRequest::Toggle)
+    }
+
+    fn reset(&self) -> fdo::Result<()> {
+        self.command(IpcRequest::Reset)
+    }
+
+    fn snooze(&self, seconds: u64) -> fdo::Result<()> {
+        self.command(IpcRequest::Snooze(Duration::from_secs(seconds)))
+    }
+
+    fn pause(&self, seconds: u64) -> fdo::Result<()> {
+        self.command(IpcRequest::Pause(Duration::from_secs(seconds)))
+    }
+
+    fn reload(&self) -> fdo::Result<()> {
+        self.command(IpcRequest::Reload)
+    }
+
+    fn list_timers(&self) -> fdo::Result<Vec<TimerTuple>> {
+        match self.request(IpcRequest::ListTimers)? {
+            IpcResponse::Timers(timers) => Ok(timers
+                .into_iter()
+                .map(|t| {
+                    let secs = t.time_left.as_secs();
+                    (
+                        t.id,
+                        t.name,
+                        secs,
+                        t.prompts,
+                        t.is_enabled,
+                        t.is_active,
+                        t.is_next,
+                    )
+                })
+                .collect()),
+            _ => Err(fdo::Error::Failed("Unexpected response".to_string())),
+        }
+    }
+
+    fn enable_timer(&self, id: String) -> fdo::Result<()> {
+        self.command(IpcRequest::Timer(id, TimerAction::Enable))
+    }
+
+    fn disable_timer(&self, id: String) -> fdo::Result<()> {
+        self.command(IpcRequest::Timer(id, TimerAction::Disable))
+    }
+
+    fn reset_timer(&self, id: String) -> fdo::Result<()> {
+        self.command(IpcRequest::Timer(id, TimerAction::Reset))
+    }
+
+    /// Seconds at the screen since the last reset, changes every second without a signal
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn elapsed(&self) -> u64 {
+        self.status_value().elapsed.as_secs()
+    }
+
+    /// Seconds until the next timer fires, zero if there is none
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn remaining(&self) -> u64 {
+        self.status_value()
+            .remaining()
+            .map_or(0, |remaining| remaining.as_secs())
+    }
+
+    #[zbus(property)]
+    fn next_timer_id(&self) -> String {
+        self.status_value().next_timer_id.unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn next_timer_name(&self) -> String {
+        self.status_value().next_timer_name.unwrap_or_default()
+    }
+
+    /// One of `active`, `break`, `frozen`, `paused` or `disabled`
+    #[zbus(property)]
+    fn state(&self) -> String {
+        self.status_value().state().to_string()
+    }
+
+    #[zbus(signal)]
+    async fn timer_fired(emitter: &SignalEmitter<'_>, id: &str, name: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn paused(emitter: &SignalEmitter<'_>, seconds: u64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn resumed(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn timers_reset(emitter: &SignalEmitter<'_>, reason: &str) -> zbus::Result<()>;
+}
+
+/// Exports the interface on the bus of the connection builder, like the session bus, and forwards
+/// the daemon events as signals
+pub async fn serve(daemon: Arc<Mutex<Daemon>>, builder: connection::Builder<'_>) -> Result<()> {
+    let mut events = daemon.lock().unwrap().events.subscribe();
+    let conn = builder
+        .name(BUS_NAME)?
+        .serve_at(
+            OBJECT_PATH,
+            BlinkInterface {
+                daemon: daemon.clone(),
+            },
+        )?
+        .build()
+        .await
+        .context("failed to connect to the bus")?;
+    log::info!("Exported D-Bus interface '{BUS_NAME}' at '{OBJECT_PATH}'");
+
+    let iface = conn
+        .object_server()
+        .interface::<_, BlinkInterface>(OBJECT_PATH)
+        .await?;
+    let emitter = iface.signal_emitter();
+    // Only emit property changes when the values actually change
+    let mut last_status: Option<Status> = None;
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        let result = match event {
+            Event::Status(status) => {
+                let last = last_status.replace(status.clone());
+                async {
+                    let iface = iface.get().await;
+                    if last
+                        .as_ref()
+                        .is_none_or(|last| last.state() != status.state())
+                    {
+                        iface.state_changed(emitter).await?;
+                    }
+                    if last
+                        .as_ref()
+                        .is_none_or(|last| last.next_timer_id != status.next_timer_id)
+                    {
+                        iface.next_timer_id_changed(emitter).await?;
+                        iface.next_timer_name_changed(emitter).await?;
+                    }
+                    Ok(())
+                }
+                .await
+            }
+            Event::TimerFired { id, name } => {
+                BlinkInterface::timer_fired(emitter, &id, &name).await
+            }
+            Event::Paused(duration) => BlinkInterface::paused(emitter, duration.as_secs()).await,
+            Event::Resumed => BlinkInterface::resumed(emitter).await,
+            Event::Reset(reason) => {
+                BlinkInterface::timers_reset(emitter, &format!("{reason:?}").to_lowercase()).await
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to emit D-Bus signal: {e}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn serve_test() {
+    use std::{
+        io::{BufRead, BufReader},
+        pin::Pin,
+        process::{Command, Stdio},
+    };
+    use zbus::export::futures_core::Stream;
+
+    // A private bus, such that the test does not depend on a session
+    let mut bus = match Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(bus) => bus,
+        Err(e) => {
+            eprintln!("Skipping D-Bus test, failed to start dbus-daemon: {e}");
+            return;
+        }
+    };
+    let mut address = String::new();
+    BufReader::new(bus.stdout.take().unwrap())
+        .read_line(&mut address)
+        .unwrap();
+    let address = address.trim().to_string();
+
+    let daemon = Arc::new(Mutex::new(super::test_daemon(
+        "timers: [{ interval: 20:00 }]",
+    )));
+    tokio::spawn({
+        let daemon = daemon.clone();
+        let builder = connection::Builder::address(address.as_str()).unwrap();
+        async move { serve(daemon, builder).await.unwrap() }
+    });
+
+    let conn = connection::Builder::address(address.as_str())
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let proxy: zbus::Proxy = zbus::proxy::Builder::new(&conn)
+        .destination(BUS_NAME)
+        .unwrap()
+        .path(OBJECT_PATH)
+        .unwrap()
+        .interface("nl.rijkvp.Blink")
+        .unwrap()
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()
+        .await
+        .unwrap();
+
+    // The name is owned once the interface is served
+    let mut status: zbus::Result<String> = proxy.call("Status", &()).await;
+    for _ in 0..50 {
+        if status.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        status = proxy.call("Status", &()).await;
+    }
+    assert!(status.unwrap().contains("20:00"));
+
+    let _: () = proxy.call("Toggle", &()).await.unwrap();
+    assert!(!daemon.lock().unwrap().is_enabled);
+    assert_eq!(
+        proxy.get_property::<String>("State").await.unwrap(),
+        "disabled"
+    );
+    let _: () = proxy.call("Toggle", &()).await.unwrap();
+    assert_eq!(
+        proxy.get_property::<String>("State").await.unwrap(),
+        "active"
+    );
+    assert_eq!(
+        proxy.get_property::<String>("NextTimerId").await.unwrap(),
+        "20:00"
+    );
+    assert_eq!(
+        proxy.get_property::<u64>("Remaining").await.unwrap(),
+        20 * 60
+    );
+
+    let mut fired = proxy.receive_signal("TimerFired").await.unwrap();
+    {
+        let mut daemon = daemon.lock().unwrap();
+        daemon.elapsed = daemon.next_timer_at;
+        let now = daemon.last_update + super::TICK_INTERVAL;
+        daemon.tick(now);
+    }
+    let next_signal = std::future::poll_fn(|cx| Pin::new(&mut fired).poll_next(cx));
+    let signal = tokio::time::timeout(Duration::from_secs(5), next_signal)
+        .await
+        .unwrap()
+        .unwrap();
+    let (id, _name): (String, String) = signal.body().deserialize().unwrap();
+    assert_eq!(id, "20:00");
+
+    bus.kill().unwrap();
+    bus.wait().unwrap();
+}
@@ -0,0 +1,1758 @@
+use activity::{ActivityConnection, ActivityUpdate};
+use anyhow::{Context, Result};
+use blink_timer::{
+    APP_NAME, ActivityMessage, ActivityState, BreakInfo, BreakStatus, DurationExt, Event,
+    InputKind, IpcRequest, IpcResponse, Status, TimerAction, TimerStatus, TypingProgress,
+    async_socket::{SocketServer, SocketStream},
+    config::{Config, NotificationAction, Timer},
+    get_unix_time, get_unix_time_ms,
+    history::{History, HistoryEntry, HistoryEvent, ResetReason},
+    state::{State, TimerProgress},
+    util,
+};
+use clap::Parser;
+use jiff::Zoned;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::{broadcast, mpsc},
+};
+
+mod activity;
+mod dbus;
+
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+// Maximum age of the saved state to restore the timers when input tracking is disabled
+const STATE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// Number of events that are buffered for a subscribed client that is not keeping up
+const EVENT_CAPACITY: usize = 64;
+/// Time after the end of a break on the break screen after which the screen is assumed to be gone
+const BREAK_SCREEN_GRACE: Duration = Duration::from_secs(60);
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Set a custom config file
+    #[clap(short, long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .init();
+    let args = Args::parse();
+
+    let config_path = args.config.unwrap_or({
+        dirs::config_dir()
+            .context("No config dir available")?
+            .join(APP_NAME)
+            .join(APP_NAME.to_string() + ".yaml")
+    });
+    log::debug!("Config path: '{}'", config_path.display());
+    let config = Config::load_or_create(config_path.clone())?;
+
+    let mut daemon = Daemon::new(config, config_path);
+    daemon.restore_state();
+    daemon.run().await?;
+    Ok(())
+}
+
+#[derive(Default, Clone)]
+struct TimerState {
+    time_left: Duration,
+    prompts: u64,
+    snoozes: u32,
+    /// Elapsed time at which a snoozed timer fires, instead of at the end of its interval
+    snoozed_until: Option<Duration>,
+    /// Elapsed time at which this timer was last reset
+    reset_at: Duration,
+    /// Whether the timer is within its schedule
+    is_active: bool,
+    is_enabled: bool,
+    /// Description of the last notification
+    description: Option<String>,
+    /// Key presses since the last reset, for typing timers
+    keystrokes: u64,
+    /// Unix time in milliseconds at which the current typing streak started
+    streak_start: Option<u64>,
+    timer: Timer,
+}
+
+impl TimerState {
+    fn new(b: Timer) -> Self {
+        Self {
+            timer: b,
+            is_active: true,
+            is_enabled: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the timer can fire
+    fn is_running(&self) -> bool {
+        self.is_active && self.is_enabled
+    }
+
+    /// Time until the end of the current interval, without the decline applied
+    fn time_left(&self, elapsed: Duration) -> Duration {
+        if self.timer.typing.is_some() {
+            // Typing timers do not fire on the elapsed time
+            return Duration::MAX;
+        }
+        let elapsed = elapsed.saturating_sub(self.reset_at);
+        if let Some(initial_delay) = self.timer.initial_delay
+            && elapsed <= initial_delay
+        {
+            // Before the initial delay
+            initial_delay - elapsed
+        } else if let Some(initial_delay) = self.timer.initial_delay {
+            // After the initial delay: at every interval, relative to when initial delay ended
+            let elapsed_since_initial = elapsed - initial_delay;
+            self.timer.interval
+                - Duration::from_secs_f64(
+                    elapsed_since_initial.as_secs_f64() % self.timer.interval.as_secs_f64(),
+                )
+        } else {
+            // No initial delay: at every interval from the start
+            self.timer.interval
+                - Duration::from_secs_f64(elapsed.as_secs_f64() % self.timer.interval.as_secs_f64())
+        }
+    }
+
+    /// Time until the timer fires without the decline applied, at the end of a snooze if it is snoozed
+    fn time_until_fired(&self, elapsed: Duration) -> Duration {
+        match self.snoozed_until {
+            Some(snoozed_until) => snoozed_until.saturating_sub(elapsed),
+            None => self.time_left(elapsed),
+        }
+    }
+
+    /// The decline function, the interval will be multiplied by 0.5 with a decline of 1.0
+    /// A snooze is not shortened by the decline
+    fn interval_mult(&self) -> f64 {
+        if self.snoozed_until.is_some() {
+            1.0
+        } else {
+            (1.0 / (1.0 + self.timer.decline)).powf(self.prompts as f64)
+        }
+    }
+
+    /// Time left until the timer fires, with the decline applied
+    fn declined_time_left(&self) -> Duration {
+        Duration::from_secs_f64(self.time_left.as_secs_f64() * self.interval_mult())
+    }
+
+    fn reset(&mut self, elapsed: Duration) {
+        self.prompts = 0;
+        self.snoozes = 0;
+        self.snoozed_until = None;
+        self.reset_at = elapsed;
+        self.keystrokes = 0;
+        self.streak_start = None;
+    }
+
+    /// Length of the current typing streak
+    fn streak(&self) -> Duration {
+        self.streak_start.map_or(Duration::ZERO, |start| {
+            Duration::from_millis(get_unix_time_ms().saturating_sub(start))
+        })
+    }
+}
+
+/// A break that is due after a timer with a break duration expired
+struct Break {
+    /// Index of the timer that started the break
+    timer: usize,
+    duration: Duration,
+    /// While the break screen shows the break, it ends the break instead of the inactivity
+    screen_until: Option<Instant>,
+}
+
+struct Daemon {
+    config: Config,
+    config_path: PathBuf,
+    /// Modification time of the config file when it was last loaded
+    config_modified: Option<SystemTime>,
+    elapsed: Duration,
+    last_update: Instant,
+    next_timer_at: Duration,
+    next_timer: Option<usize>,
+    timers: Vec<TimerState>,
+    on_break: Option<Break>,
+    is_enabled: bool,
+    /// Unix time at which the timers are enabled again after a pause
+    paused_until: Option<u64>,
+    is_frozen: bool,
+    /// Unix time in milliseconds of the last input
+    last_input: u64,
+    /// Whether the activity source is connected, input tracking is suspended otherwise
+    activity_connected: bool,
+    /// Last update from the activity source since it was connected
+    activity: Option<ActivityMessage>,
+    /// Whether the activity source was lost, to notify when it is restored
+    activity_lost: bool,
+    /// Whether the activity source counts the key presses and pointer movement
+    counts_input: bool,
+    /// Activity updates of about the last minute, to determine the input rates
+    activity_samples: VecDeque<(u64, ActivityMessage)>,
+    state_path: Option<PathBuf>,
+    last_save: Instant,
+    history: Option<History>,
+    events: broadcast::Sender<Event>,
+    /// Buttons clicked on notifications, with the id of the timer
+    action_sender: mpsc::UnboundedSender<(String, NotificationAction)>,
+    action_receiver: Option<mpsc::UnboundedReceiver<(String, NotificationAction)>>,
+}
+
+impl Daemon {
+    fn new(config: Config, config_path: PathBuf) -> Self {
+        let state = config
+            .timers
+            .iter()
+            .map(|b| TimerState::new(b.clone()))
+            .collect();
+        let (action_sender, action_receiver) = mpsc::unbounded_channel();
+        Self {
+            config,
+            config_modified: modified_time(&config_path),
+            config_path,
+            elapsed: Duration::ZERO,
+            last_update: Instant::now(),
+            next_timer_at: Duration::MAX,
+            next_timer: None,
+            timers: state,
+            on_break: None,
+            is_enabled: true,
+            paused_until: None,
+            is_frozen: false,
+            last_input: get_unix_time_ms(),
+            activity_connected: false,
+            activity: None,
+            activity_lost: false,
+            counts_input: false,
+            activity_samples: VecDeque::new(),
+            state_path: blink_timer::state_path(),
+            last_save: Instant::now(),
+            history: blink_timer::history_path().map(History::new),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            action_sender,
+            action_receiver: Some(action_receiver),
+        }
+    }
+
+    /// Restores the state saved by a previous run of the daemon
+    fn restore_state(&mut self) {
+        let Some(path) = &self.state_path else {
+            return;
+        };
+        let state = match State::load(path) {
+            Ok(Some(state)) => state,
+            Ok(None) => return,
+            Err(e) => {
+                log::warn!("Failed to load state: {e:?}");
+                return;
+            }
+        };
+        self.is_enabled = state.is_enabled;
+        self.paused_until = state.paused_until;
+
+        let offline = Duration::from_secs(get_unix_time().saturating_sub(state.saved_at));
+        let timeout = self
+            .config
+            .input_tracking
+            .as_ref()
+            .map_or(STATE_TIMEOUT, |i| i.reset_after);
+        if offline >= timeout {
+            log::info!("Not restoring timers (saved {} ago)", offline.display());
+            return;
+        }
+
+        // The time the daemon was not running counts as elapsed, unless it would have frozen the timers
+        let frozen = !state.is_enabled
+            || self
+                .config
+                .input_tracking
+                .as_ref()
+                .is_some_and(|i| offline > i.pause_after);
+        self.elapsed = state.elapsed + if frozen { Duration::ZERO } else { offline };
+
+        let timers_match = state.timers.len() == self.timers.len()
+            && state
+                .timers
+                .iter()
+                .zip(&self.timers)
+                .all(|(progress, item)| progress.id == item.timer.id());
+        if timers_match {
+            for (progress, item) in state.timers.iter().zip(self.timers.iter_mut()) {
+                item.is_enabled = progress.is_enabled;
+                item.prompts = progress.prompts;
+                item.snoozes = progress.snoozes;
+                item.snoozed_until = progress.snoozed_until;
+                item.reset_at = progress.reset_at;
+                item.keystrokes = progress.keystrokes;
+                item.streak_start = progress.streak_start;
+            }
+            self.next_timer_at = state.next_timer_at;
+            self.next_timer = state.next_timer;
+        } else {
+            log::info!("Timers have changed, only restoring the elapsed time");
+        }
+        log::info!(
+            "Restored state (elapsed {}, saved {} ago)",
+            self.elapsed.display(),
+            offline.display()
+        );
+    }
+
+    /// Appends an event to the history log
+    fn record(&self, event: HistoryEvent) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let entry = HistoryEntry {
+            time: get_unix_time(),
+            elapsed: self.elapsed,
+            event,
+        };
+        if let Err(e) = history.append(&entry) {
+            log::error!(
+                "Failed to write history to '{}': {e:?}",
+                history.path().display()
+            );
+        }
+    }
+
+    /// Sends an event to all subscribed clients
+    fn emit(&self, event: Event) {
+        // Sending only fails when there are no subscribers
+        let _ = self.events.send(event);
+    }
+
+    fn save_state(&self) {
+        let Some(path) = &self.state_path else {
+            return;
+        };
+        let state = State {
+            saved_at: get_unix_time(),
+            elapsed: self.elapsed,
+            next_timer_at: self.next_timer_at,
+            next_timer: self.next_timer,
+            is_enabled: self.is_enabled,
+            paused_until: self.paused_until,
+            timers: self
+                .timers
+                .iter()
+                .map(|item| TimerProgress {
+                    id: item.timer.id(),
+                    is_enabled: item.is_enabled,
+                    prompts: item.prompts,
+                    snoozes: item.snoozes,
+                    snoozed_until: item.snoozed_until,
+                    reset_at: item.reset_at,
+                    keystrokes: item.keystrokes,
+                    streak_start: item.streak_start,
+                })
+                .collect(),
+        };
+        if let Err(e) = state.save(path) {
+            log::error!("Failed to save state: {e:?}");
+        } else {
+            log::debug!("Saved state to '{}'", path.display());
+        }
+    }
+
+    async fn run(mut self) -> Result<()> {
+        let mut listener = SocketServer::create(blink_timer::socket_path(), true)
+            .await
+            .context("failed to create socket server")?;
+        // Connects in the background, such that blinkd also starts without the activity source
+        let mut activity_connection = self
+            .config
+            .input_tracking
+            .as_ref()
+            .map(|input_tracking| ActivityConnection::new(input_tracking.source));
+
+        if self.config.startup_notification {
+            util::show_notification("Blink".to_string(), "Blink is running.".to_string(), None);
+        }
+
+        let mut action_receiver = self.action_receiver.take().expect("daemon is run once");
+        let mut sigterm = signal(SignalKind::terminate())?;
+        let mut sigint = signal(SignalKind::interrupt())?;
+        let mut sighup = signal(SignalKind::hangup())?;
+        let daemon = Arc::new(Mutex::new(self));
+        {
+            // The next timer is already known when the state was restored
+            let mut daemon = daemon.lock().unwrap();
+            if daemon.update_active() || daemon.next_timer.is_none() {
+                daemon.update_timer();
+            }
+            daemon.record(HistoryEvent::Start);
+        }
+
+        if daemon.lock().unwrap().config.dbus {
+            tokio::spawn({
+                let daemon = daemon.clone();
+                async move {
+                    let result = match zbus::connection::Builder::session() {
+                        Ok(builder) => dbus::serve(daemon, builder).await,
+                        Err(e) => Err(e.into()),
+                    };
+                    if let Err(e) = result {
+                        log::error!("Failed to serve D-Bus interface: {e:?}");
+                    }
+                }
+            });
+        }
+
+        tokio::spawn({
+            let daemon = daemon.clone();
+            async move {
+                let mut interval = tokio::time::interval(TICK_INTERVAL);
+                interval.tick().await; // First tick completes immediately
+                loop {
+                    interval.tick().await;
+                    let now = Instant::now();
+                    let mut daemon = daemon.lock().unwrap();
+                    daemon.tick(now);
+                    drop(daemon);
+                }
+            }
+        });
+
+        loop {
+            tokio::select! {
+                _ = sigterm.recv() => {
+                    log::info!("Received SIGTERM, shutting down");
+                    break;
+                }
+                _ = sigint.recv() => {
+                    log::info!("Received SIGINT, shutting down");
+                    break;
+                }
+                _ = sighup.recv() => {
+                    log::info!("Received SIGHUP, reloading config");
+                    if let Err(e) = daemon.lock().unwrap().reload() {
+                        log::error!("Failed to reload config: {e:?}");
+                    }
+                }
+                Some((id, action)) = action_receiver.recv() => {
+                    daemon.lock().unwrap().handle_notification_action(&id, action);
+                }
+                Ok(client_stream) = listener.accept_client() => {
+                    tokio::spawn({
+                        let daemon = daemon.clone();
+                        async move {
+                            if let Err(e) = Self::handle_client(client_stream, daemon).await {
+                                log::error!("Failed to handle client: {e:?}");
+                            }
+                        }
+                    });
+                }
+                update = async {
+                    if let Some(connection) = &mut activity_connection {
+                        connection.recv().await
+                    } else {
+                        std::future::pending().await
+                    }
+                } => {
+                    daemon.lock().unwrap().handle_activity_update(update);
+                }
+            }
+        }
+        let daemon = daemon.lock().unwrap();
+        daemon.save_state();
+        daemon.record(HistoryEvent::Stop);
+        Ok(())
+    }
+
+    fn tick(&mut self, now: Instant) {
+        let delta = now.duration_since(self.last_update);
+        self.last_update = now;
+
+        if self.config.reload_on_change {
+            let modified = modified_time(&self.config_path);
+            if modified != self.config_modified {
+                self.config_modified = modified;
+                log::info!("Config file changed, reloading");
+                if let Err(e) = self.reload() {
+                    log::error!("Failed to reload config: {e:?}");
+                }
+            }
+        }
+
+        if now.duration_since(self.last_save) >= SAVE_INTERVAL {
+            self.save_state();
+            self.last_save = now;
+        }
+
+        if self
+            .paused_until
+            .is_some_and(|paused_until| get_unix_time() >= paused_until)
+        {
+            self.resume();
+        }
+
+        // Check for big delay between ticks, likely caused when the system was suspended
+        // This also counts as input inactivity
+        if self
+            .config
+            .input_tracking
+            .as_ref()
+            .map(|i| i.reset_after)
+            .is_some_and(|timeout_reset| delta >= timeout_reset)
+        {
+            if self.elapsed > Duration::ZERO {
+                log::info!("Resetting timer (update delta of {})", delta.display());
+                self.reset(ResetReason::Suspend);
+                self.emit(Event::Status(self.status()));
+                return;
+            }
+        } else if let Some(input_tracking) = &self.config.input_tracking
+            && self.activity_connected
+        {
+            // Reset or freeze the timer based on input tracking config
+            let elapsed_since_input = self.idle_time();
+            if elapsed_since_input >= input_tracking.reset_after && self.elapsed > Duration::ZERO {
+                log::info!("Resetting timer (input timeout {elapsed_since_input:?})");
+                self.reset(ResetReason::Inactivity);
+                self.is_frozen = true;
+                self.emit(Event::Status(self.status()));
+                return;
+            }
+            if !self.is_frozen && elapsed_since_input > input_tracking.pause_after {
+                log::trace!("Frozen");
+                self.is_frozen = true;
+                self.emit(Event::Frozen);
+            } else if self.is_frozen && elapsed_since_input < Duration::from_secs(3) {
+                log::trace!("Unfrozen");
+                self.is_frozen = false;
+                self.emit(Event::Unfrozen);
+            }
+        };
+
+        if let Some(on_break) = &self.on_break
+            && self.activity_connected
+            && on_break.screen_until.is_none_or(|until| now >= until)
+            && self.idle_time() >= on_break.duration
+        {
+            log::info!("Break taken ({})", on_break.duration.display());
+            self.take_break(on_break.timer, on_break.duration);
+        }
+
+        if !self.is_frozen && self.is_enabled {
+            self.elapsed += delta;
+            log::trace!(
+                "Tick {}/{}",
+                self.elapsed.display(),
+                self.next_timer_at.display()
+            );
+        }
+
+        if !self.is_frozen && self.is_enabled {
+            self.update_typing();
+        }
+
+        if self.update_active() {
+            log::info!("Active timers changed by their schedule");
+            self.update_timer();
+        }
+
+        if self.elapsed >= self.next_timer_at
+            && let Some(index) = self.next_timer
+        {
+            self.notify(index);
+            self.update_timer();
+        }
+        self.emit(Event::Status(self.status()));
+    }
+
+    fn reset(&mut self, reason: ResetReason) {
+        self.record(HistoryEvent::Reset { reason });
+        self.emit(Event::Reset(reason));
+        self.elapsed = Duration::ZERO;
+        self.on_break = None;
+        for item in self.timers.iter_mut() {
+            item.reset(Duration::ZERO);
+        }
+        self.update_timer();
+    }
+
+    /// Reloads the config file and rebuilds the timers, keeping the progress of unchanged timers
+    fn reload(&mut self) -> Result<()> {
+        let mut config = Config::load(&self.config_path)?;
+        self.config_modified = modified_time(&self.config_path);
+        if self.config.input_tracking.is_none() && config.input_tracking.is_some() {
+            log::warn!("Enabling input tracking requires a restart of the daemon");
+            config.input_tracking = None;
+            // Typing timers never progress without input tracking
+            config.timers.retain(|timer| {
+                if timer.typing.is_some() {
+                    log::warn!(
+                        "Ignoring typing timer '{}' until input tracking is enabled",
+                        timer.id()
+                    );
+                }
+                timer.typing.is_none()
+            });
+        }
+        if let (Some(old), Some(new)) = (&self.config.input_tracking, &mut config.input_tracking)
+            && old.source != new.source
+        {
+            log::warn!("Changing the activity source requires a restart of the daemon");
+            new.source = old.source;
+        }
+        if self.config.dbus != config.dbus {
+            log::warn!("Changing the D-Bus interface requires a restart of the daemon");
+            config.dbus = self.config.dbus;
+        }
+
+        // Match the new timers with the old ones by id
+        let mut old_timers: Vec<Option<TimerState>> = self.timers.drain(..).map(Some).collect();
+        let mut old_indices = Vec::with_capacity(config.timers.len());
+        for timer in &config.timers {
+            let old_index = old_timers
+                .iter()
+                .position(|old| old.as_ref().is_some_and(|old| old.timer.id() == timer.id()));
+            let mut item = TimerState::new(timer.clone());
+            if let Some(old) = old_index.and_then(|i| old_timers[i].take()) {
+                item.is_enabled = old.is_enabled;
+                item.prompts = old.prompts;
+                item.snoozes = old.snoozes;
+                item.snoozed_until = old.snoozed_until;
+                item.reset_at = old.reset_at;
+                item.keystrokes = old.keystrokes;
+                item.streak_start = old.streak_start;
+            }
+            self.timers.push(item);
+            old_indices.push(old_index);
+        }
+
+        // Keep a break of an unchanged timer
+        self.on_break = self.on_break.take().and_then(|on_break| {
+            let timer = old_indices
+                .iter()
+                .position(|old_index| *old_index == Some(on_break.timer))?;
+            Some(Break {
+                timer,
+                duration: self.timers[timer].timer.break_duration?,
+                screen_until: on_break.screen_until,
+            })
+        });
+
+        log::info!(
+            "Reloaded config from '{}' ({} timers)",
+            self.config_path.display(),
+            self.timers.len()
+        );
+        self.config = config;
+        self.next_timer = None;
+        self.update_timer();
+        Ok(())
+    }
+
+    /// Enables the timers again after a pause
+    fn resume(&mut self) {
+        self.paused_until = None;
+        self.is_enabled = true;
+        log::info!("Resumed after pause");
+        self.record(HistoryEvent::Resume);
+        self.emit(Event::Resumed);
+        util::show_notification("Blink".to_string(), "Blink is resumed.".to_string(), None);
+    }
+
+    fn handle_activity_update(&mut self, update: ActivityUpdate) {
+        let Some(input_tracking) = &self.config.input_tracking else {
+            return;
+        };
+        match update {
+            ActivityUpdate::Connected {
+                has_input_kinds,
+                has_input_counts,
+            } => {
+                let source = input_tracking.source;
+                let all_inputs = InputKind::ALL
+                    .iter()
+                    .all(|kind| input_tracking.inputs.contains(kind));
+                if !has_input_kinds && !all_inputs {
+                    log::warn!(
+                        "{source:?} does not tell the kinds of input apart, all input counts as activity"
+                    );
+                }
+                if !has_input_counts && self.timers.iter().any(|item| item.timer.typing.is_some()) {
+                    log::warn!(
+                        "{source:?} does not count the key presses, typing timers only fire on their streak"
+                    );
+                }
+                self.counts_input = has_input_counts;
+                self.activity_samples.clear();
+                if self.activity_lost {
+                    log::info!("Activity source reconnected, resuming input tracking");
+                    self.activity_lost = false;
+                    self.emit(Event::ActivityRestored);
+                }
+                self.activity_connected = true;
+                // The stale input time of before the connection should not freeze the timers
+                self.last_input = get_unix_time_ms();
+            }
+            ActivityUpdate::Activity(activity) => self.update_activity(activity),
+            ActivityUpdate::Disconnected => {
+                log::warn!("Input tracking is suspended until the activity source reconnects");
+                self.activity_connected = false;
+                self.activity_lost = true;
+                self.activity = None;
+                // The timers should not stay frozen while the input is unknown
+                if self.is_frozen {
+                    self.is_frozen = false;
+                    self.emit(Event::Unfrozen);
+                }
+                self.emit(Event::ActivityLost);
+            }
+        }
+    }
+
+    /// Handles an update from the activity source
+    fn update_activity(&mut self, activity: ActivityMessage) {
+        let Some(input_tracking) = &self.config.input_tracking else {
+            return;
+        };
+        if let Some(last_input) = activity.last_input_of(&input_tracking.inputs) {
+            self.last_input = last_input;
+        }
+
+        // The first update after connecting is the baseline of the counters
+        let keystrokes = self.activity.map_or(0, |last| {
+            activity.keystrokes.saturating_sub(last.keystrokes)
+        });
+        if keystrokes > 0 && self.is_enabled {
+            for item in self
+                .timers
+                .iter_mut()
+                .filter(|item| item.timer.typing.is_some() && item.is_running())
+            {
+                item.keystrokes += keystrokes;
+            }
+        }
+        self.activity = Some(activity);
+
+        let now = get_unix_time();
+        self.activity_samples.push_back((now, activity));
+        // Keep one sample from before the last minute as the start of the window
+        while self
+            .activity_samples
+            .get(1)
+            .is_some_and(|(time, _)| now.saturating_sub(*time) >= 60)
+        {
+            self.activity_samples.pop_front();
+        }
+    }
+
+    /// Updates the typing streaks and fires the typing timers that reached their limit
+    fn update_typing(&mut self) {
+        let Some(activity) = self.activity else {
+            return;
+        };
+        let now = get_unix_time_ms();
+        for index in 0..self.timers.len() {
+            let item = &mut self.timers[index];
+            let Some(typing) = &item.timer.typing else {
+                continue;
+            };
+            if !item.is_running() {
+                continue;
+            }
+            let keyboard_idle = Duration::from_millis(now.saturating_sub(activity.keyboard));
+            if keyboard_idle >= typing.micro_pause() {
+                item.streak_start = None;
+            } else if item.streak_start.is_none() {
+                item.streak_start = Some(activity.keyboard);
+            }
+
+            let keystrokes_reached = typing
+                .keystrokes
+                .is_some_and(|limit| item.keystrokes >= limit);
+            let streak_reached = typing.streak.is_some_and(|limit| item.streak() >= limit);
+            if keystrokes_reached || streak_reached {
+                log::info!(
+                    "Typing limit of timer '{}' reached ({} keys, streak of {})",
+                    item.timer.id(),
+                    item.keystrokes,
+                    item.streak().display()
+                );
+                item.keystrokes = 0;
+                item.streak_start = None;
+                self.notify(index);
+            }
+        }
+    }
+
+    /// Key presses and pointer movement in the last minute
+    fn input_rates(&self) -> Option<(u64, u64)> {
+        if !self.counts_input {
+            return None;
+        }
+        let (Some((_, first)), Some((_, last))) =
+            (self.activity_samples.front(), self.activity_samples.back())
+        else {
+            return Some((0, 0));
+        };
+        Some((
+            last.keystrokes.saturating_sub(first.keystrokes),
+            last.pointer_motion.saturating_sub(first.pointer_motion),
+        ))
+    }
+
+    /// Time since the last input event reported by the activity daemon
+    fn idle_time(&self) -> Duration {
+        Duration::from_millis(get_unix_time_ms().saturating_sub(self.last_input))
+    }
+
+    /// Updates which timers are within their schedule, returns whether this has changed
+    fn update_active(&mut self) -> bool {
+        let now = Zoned::now();
+        let mut changed = false;
+        for item in self.timers.iter_mut() {
+            let is_active = item
+                .timer
+                .schedule
+                .as_ref()
+                .is_none_or(|schedule| schedule.is_active(&now));
+            changed |= item.is_active != is_active;
+            item.is_active = is_active;
+        }
+        changed
+    }
+
+    /// Time until the first timer outside of its schedule becomes active
+    fn next_active(&self) -> Option<Duration> {
+        let now = Zoned::now();
+        self.timers
+            .iter()
+            .filter(|item| !item.is_active)
+            .filter_map(|item| item.timer.schedule.as_ref()?.time_until_active(&now))
+            .min()
+    }
+
+    fn update_timer(&mut self) {
+        self.update_active();
+
+        // Determine the next timer
+        for item in self.timers.iter_mut() {
+            item.time_left = item.time_until_fired(self.elapsed);
+        }
+
+        // Order first by time left and then by interval (timers with longer intervals have priority)
+        let next_index = self
+            .timers
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_running() && item.timer.typing.is_none())
+            .min_by(|(_, a), (_, b)| {
+                a.time_left
+                    .cmp(&b.time_left)
+                    .then(b.timer.interval.cmp(&a.timer.interval))
+            })
+            .map(|(index, _)| index);
+
+        // A timer that would fire shortly before a timer that satisfies it is suppressed
+        let next_index = next_index.map(|index| {
+            let next = &self.timers[index];
+            self.timers
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| {
+                    item.is_running()
+                        && item.timer.typing.is_none()
+                        && item.timer.satisfies.contains(&next.timer.id())
+                        && item.timer.suppress_window.is_some_and(|window| {
+                            item.declined_time_left()
+                                .saturating_sub(next.declined_time_left())
+                                <= window
+                        })
+                })
+                .min_by_key(|(_, item)| item.declined_time_left())
+                .map_or(index, |(suppressor, item)| {
+                    log::debug!(
+                        "Suppressed timer '{}' by timer '{}'",
+                        next.timer.id(),
+                        item.timer.id()
+                    );
+                    suppressor
+                })
+        });
+
+        if let Some(index) = next_index {
+            let next = &self.timers[index];
+            let interval = next.declined_time_left();
+            log::debug!(
+                "Next interval: {}, multiplier: {} (prompt: {})",
+                interval.display(),
+                next.interval_mult(),
+                next.prompts
+            );
+
+            self.next_timer_at = self.elapsed + interval;
+            self.next_timer = Some(index);
+        } else if self.timers.is_empty() {
+            log::error!("No timers found! Make sure to specify at least one in the config.");
+        } else {
+            log::info!("No timers are active according to their schedule");
+            self.next_timer_at = Duration::MAX;
+            self.next_timer = None;
+        }
+    }
+
+    fn notify(&mut self, index: usize) {
+        self.timers[index].prompts += 1;
+        self.timers[index].snoozed_until = None;
+        let timer = self.timers[index].timer.clone();
+        let id = timer.id();
+        log::info!("Timer expired: {id}\x07");
+        self.record(HistoryEvent::Prompt { id: id.clone() });
+        self.emit(Event::TimerFired {
+            id: id.clone(),
+            name: timer.name(),
+        });
+
+        // Breaks can only be tracked when input tracking is enabled
+        if let Some(duration) = timer.break_duration
+            && self.config.input_tracking.is_some()
+        {
+            if self
+                .on_break
+                .as_ref()
+                .is_none_or(|b| b.duration <= duration)
+            {
+                log::info!("Break started ({})", duration.display());
+                self.on_break = Some(Break {
+                    timer: index,
+                    duration,
+                    screen_until: None,
+                });
+            }
+        } else {
+            // Without break tracking, the notification itself counts for the satisfied timers
+            self.satisfy(index);
+        }
+
+        if let Some(notification) = timer.notification {
+            let description = {
+                if !notification.descriptions.is_empty() {
+                    let rand_index = rand::random_range(0..notification.descriptions.len());
+                    &notification.descriptions[rand_index]
+                } else {
+                    "{} elapsed"
+                }
+            };
+            let description = util::format_string(description, &self.elapsed.display().to_string());
+            self.timers[index].description = Some(description.clone());
+            if notification.actions.is_empty() {
+                util::show_notification(notification.title, description, notification.timeout);
+            } else {
+                let actions = notification
+                    .actions
+                    .iter()
+                    .map(|action| {
+                        let label = match action {
+                            NotificationAction::Snooze => {
+                                format!("Snooze {}", notification.snooze_duration().display())
+                            }
+                            NotificationAction::Skip => "Skip".to_string(),
+                            NotificationAction::StartBreak => "Start break".to_string(),
+                        };
+                        (action.id().to_string(), label)
+                    })
+                    .collect();
+                let sender = self.action_sender.clone();
+                let id = id.clone();
+                util::show_notification_with_actions(
+                    notification.title,
+                    description,
+                    notification.timeout,
+                    actions,
+                    move |action| {
+                        if let Some(action) = NotificationAction::from_id(action) {
+                            // Fails only when the daemon is shutting down
+                            let _ = sender.send((id, action));
+                        }
+                    },
+                );
+            }
+        }
+
+        if let Some(sound) = timer.sound {
+            log::info!("Playing sound '{}'", sound.display());
+            util::play_sound(sound);
+        }
+
+        if let Some(cmd) = timer.command {
+            log::info!("Running command '{cmd}'");
+            util::exec_command(cmd);
+        }
+
+        if let Some(terminal) = timer.break_screen {
+            let cmd = format!(
+                "{} break --timer {}",
+                util::shell_quote(&blinkctl_command()),
+                util::shell_quote(&id)
+            );
+            let cmd = util::format_string(&terminal, &cmd);
+            log::info!("Opening break screen '{cmd}'");
+            util::exec_command(cmd);
+        }
+    }
+
+    /// Resets a timer and the timers it satisfies after its break was taken
+    fn take_break(&mut self, index: usize, duration: Duration) {
+        let elapsed = self.elapsed;
+        let item = &mut self.timers[index];
+        item.reset(elapsed);
+        let event = HistoryEvent::BreakTaken {
+            id: item.timer.id(),
+            duration,
+        };
+        self.record(event);
+        if self.on_break.as_ref().is_some_and(|b| b.timer == index) {
+            self.on_break = None;
+        }
+        self.satisfy(index);
+        self.update_timer();
+    }
+
+    /// Resets the timers that are satisfied by a break of the given timer
+    fn satisfy(&mut self, index: usize) {
+        let satisfies = self.timers[index].timer.satisfies.clone();
+        if satisfies.is_empty() {
+            return;
+        }
+        let elapsed = self.elapsed;
+        for item in self
+            .timers
+            .iter_mut()
+            .filter(|item| satisfies.contains(&item.timer.id()))
+        {
+            log::debug!("Break satisfies timer '{}'", item.timer.id());
+            item.reset(elapsed);
+        }
+        if self
+            .on_break
+            .as_ref()
+            .is_some_and(|b| satisfies.contains(&self.timers[b.timer].timer.id()))
+        {
+            self.on_break = None;
+        }
+    }
+
+    /// Postpones the break that is due, or else the next timer, without increasing its prompts
+    fn snooze(&mut self, duration: Duration) -> IpcResponse {
+        let Some(index) = self.on_break.as_ref().map(|b| b.timer).or(self.next_timer) else {
+            return IpcResponse::Error("No timer to snooze".to_string());
+        };
+        self.snooze_timer(index, duration)
+    }
+
+    /// Time until a timer fires with the decline applied, zero while its break is due
+    fn time_until_due(&self, index: usize) -> Duration {
+        let item = &self.timers[index];
+        if self.on_break.as_ref().is_some_and(|b| b.timer == index) || item.timer.typing.is_some() {
+            // Typing timers are only due after they fired
+            Duration::ZERO
+        } else if self.next_timer == Some(index) {
+            self.next_timer_at.saturating_sub(self.elapsed)
+        } else {
+            let time_left = item.time_until_fired(self.elapsed);
+            Duration::from_secs_f64(time_left.as_secs_f64() * item.interval_mult())
+        }
+    }
+
+    /// Postpones a timer until at least the given duration from now, a snooze never makes it fire
+    /// earlier and other timers can still fire before it
+    fn snooze_timer(&mut self, index: usize, duration: Duration) -> IpcResponse {
+        let snooze = self.time_until_due(index).max(duration);
+        let next = &mut self.timers[index];
+        if let Some(limit) = next.timer.snooze_limit
+            && next.snoozes >= limit
+        {
+            return IpcResponse::Error(format!(
+                "Snooze limit of {limit} reached for timer '{}'",
+                next.timer.id()
+            ));
+        }
+        next.snoozes += 1;
+        next.snoozed_until = Some(self.elapsed + snooze);
+        log::info!(
+            "Snoozed timer '{}' for {} ({} times)",
+            next.timer.id(),
+            snooze.display(),
+            next.snoozes
+        );
+        self.update_timer();
+        IpcResponse::Ok
+    }
+
+    /// Handles a button that was clicked on the notification of a timer
+    fn handle_notification_action(&mut self, id: &str, action: NotificationAction) {
+        let Some(index) = self.timers.iter().position(|item| item.timer.id() == id) else {
+            log::warn!("Notification action for unknown timer '{id}'");
+            return;
+        };
+        log::info!("Notification action '{}' of timer '{id}'", action.id());
+        let timer = self.timers[index].timer.clone();
+        match action {
+            NotificationAction::Snooze => {
+                let duration = timer
+                    .notification
+                    .as_ref()
+                    .map(|n| n.snooze_duration())
+                    .unwrap_or_default();
+                if let IpcResponse::Error(e) = self.snooze_timer(index, duration) {
+                    log::warn!("{e}");
+                    util::show_notification("Blink".to_string(), e, None);
+                }
+            }
+            NotificationAction::Skip => {
+                let elapsed = self.elapsed;
+                self.timers[index].reset(elapsed);
+                self.record(HistoryEvent::BreakSkipped { id: timer.id() });
+                if self.on_break.as_ref().is_some_and(|b| b.timer == index) {
+                    self.on_break = None;
+                }
+                self.update_timer();
+            }
+            NotificationAction::StartBreak => {
+                self.take_break(index, timer.break_duration.unwrap_or_default());
+            }
+        }
+    }
+
+    /// Starts a break on the break screen, which tracks the break instead of the input
+    fn begin_break(&mut self, id: Option<String>, duration: Option<Duration>) -> IpcResponse {
+        let index = match id {
+            Some(id) => match self.timers.iter().position(|item| item.timer.id() == id) {
+                Some(index) => index,
+                None => return IpcResponse::Error(format!("No timer with id '{id}'")),
+            },
+            None => match self.on_break.as_ref().map(|b| b.timer).or(self.next_timer) {
+                Some(index) => index,
+                None => return IpcResponse::Error("No timer to take a break for".to_string()),
+            },
+        };
+        let item = &self.timers[index];
+        let Some(duration) = duration.or(item.timer.break_duration) else {
+            return IpcResponse::Error(format!(
+                "Timer '{}' has no break duration",
+                item.timer.id()
+            ));
+        };
+        log::info!(
+            "Break screen started for timer '{}' ({})",
+            item.timer.id(),
+            duration.display()
+        );
+        let info = BreakInfo {
+            id: item.timer.id(),
+            name: item.timer.name(),
+            duration,
+            description: item.description.clone(),
+        };
+        // The break stays due until the screen completes it, in case the screen is closed
+        let screen_until = self.last_update + duration + BREAK_SCREEN_GRACE;
+        if let Some(on_break) = self.on_break.as_mut().filter(|b| b.timer == index) {
+            on_break.screen_until = Some(screen_until);
+        }
+        IpcResponse::Break(info)
+    }
+
+    fn end_break(&mut self, id: &str, duration: Duration, completed: bool) -> IpcResponse {
+        let Some(index) = self.timers.iter().position(|item| item.timer.id() == id) else {
+            return IpcResponse::Error(format!("No timer with id '{id}'"));
+        };
+        if completed {
+            log::info!("Break completed on break screen ({})", duration.display());
+            self.take_break(index, duration);
+        } else {
+            log::info!("Break aborted on break screen after {}", duration.display());
+            self.record(HistoryEvent::BreakAborted {
+                id: id.to_string(),
+                duration,
+            });
+            if let Some(on_break) = self.on_break.as_mut().filter(|b| b.timer == index) {
+                on_break.screen_until = None;
+            }
+        }
+        IpcResponse::Ok
+    }
+
+    fn status(&self) -> Status {
+        let next = self.next_timer.map(|index| &self.timers[index].timer);
+        Status {
+            elapsed: self.elapsed,
+            next_timer: self.next_timer_at,
+            next_timer_id: next.map(Timer::id),
+            next_timer_name: next.map(Timer::name),
+            is_enabled: self.is_enabled,
+            is_frozen: self.is_frozen,
+            idle: self.activity_connected.then(|| self.idle_time()),
+            on_break: self.on_break.as_ref().map(|on_break| BreakStatus {
+                remaining: on_break.duration.saturating_sub(self.idle_time()),
+                duration: on_break.duration,
+            }),
+            paused: self.paused_until.map(|paused_until| {
+                Duration::from_secs(paused_until.saturating_sub(get_unix_time()))
+            }),
+            next_active: self.next_active(),
+            typing_rate: self.input_rates().map(|(keystrokes, _)| keystrokes),
+            pointer_rate: self.input_rates().map(|(_, pointer)| pointer),
+            activity: self.config.input_tracking.as_ref().map(|_| {
+                if self.activity_connected {
+                    ActivityState::Connected
+                } else {
+                    ActivityState::Disconnected
+                }
+            }),
+        }
+    }
+
+    fn list_timers(&self) -> Vec<TimerStatus> {
+        self.timers
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let is_next = self.next_timer == Some(index);
+                TimerStatus {
+                    id: item.timer.id(),
+                    name: item.timer.name(),
+                    // The next timer includes the decline and snoozes
+                    time_left: if is_next {
+                        self.next_timer_at.saturating_sub(self.elapsed)
+                    } else {
+                        item.time_until_fired(self.elapsed)
+                    },
+                    prompts: item.prompts,
+                    is_enabled: item.is_enabled,
+                    is_active: item.is_active,
+                    is_next,
+                    typing: item.timer.typing.as_ref().map(|typing| TypingProgress {
+                        keystrokes: item.keystrokes,
+                        keystroke_limit: typing.keystrokes,
+                        streak: item.streak(),
+                        streak_limit: typing.streak,
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    fn handle_timer_action(&mut self, id: &str, action: TimerAction) -> IpcResponse {
+        let Some(index) = self.timers.iter().position(|item| item.timer.id() == id) else {
+            return IpcResponse::Error(format!("No timer with id '{id}'"));
+        };
+        let elapsed = self.elapsed;
+        let item = &mut self.timers[index];
+        match action {
+            TimerAction::Enable => item.is_enabled = true,
+            TimerAction::Disable => {
+                item.is_enabled = false;
+                if self.on_break.as_ref().is_some_and(|b| b.timer == index) {
+                    self.on_break = None;
+                }
+            }
+            TimerAction::Reset => item.reset(elapsed),
+        }
+        log::info!("Timer '{id}': {action:?}");
+        self.update_timer();
+        IpcResponse::Ok
+    }
+
+    async fn handle_client(mut stream: SocketStream, daemon: Arc<Mutex<Daemon>>) -> Result<()> {
+        let msg: IpcRequest = stream.recv().await?;
+        if let IpcRequest::Subscribe = msg {
+            return Self::handle_subscriber(stream, daemon).await;
+        }
+        let resp = {
+            let mut daemon = daemon.lock().unwrap();
+            daemon.handle_msg(msg)?
+        };
+        stream.send(resp).await?;
+        Ok(())
+    }
+
+    /// Streams events to a client until it disconnects
+    async fn handle_subscriber(mut stream: SocketStream, daemon: Arc<Mutex<Daemon>>) -> Result<()> {
+        let (mut events, status) = {
+            let daemon = daemon.lock().unwrap();
+            (daemon.events.subscribe(), daemon.status())
+        };
+        log::debug!("Client subscribed to events");
+        let mut event = Event::Status(status);
+        loop {
+            if stream.send(IpcResponse::Event(event)).await.is_err() {
+                log::debug!("Subscribed client disconnected");
+                return Ok(());
+            }
+            event = loop {
+                match events.recv().await {
+                    Ok(event) => break event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Subscribed client is lagging, skipped {skipped} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                }
+            };
+        }
+    }
+
+    fn handle_msg(&mut self, msg: IpcRequest) -> Result<IpcResponse> {
+        Ok(match msg {
+            IpcRequest::Status => IpcResponse::Status(self.status()),
+            IpcRequest::Toggle => {
+                self.paused_until = None;
+                self.is_enabled = !self.is_enabled;
+                log::info!("Set enabled to: {}", self.is_enabled);
+                self.record(HistoryEvent::Toggle {
+                    enabled: self.is_enabled,
+                });
+                self.emit(Event::Toggled {
+                    enabled: self.is_enabled,
+                });
+                IpcResponse::Ok
+            }
+            IpcRequest::Reset => {
+                self.reset(ResetReason::Manual);
+                IpcResponse::Ok
+            }
+            IpcRequest::Snooze(duration) => self.snooze(duration),
+            IpcRequest::Reload => match self.reload() {
+                Ok(()) => IpcResponse::Ok,
+                Err(e) => IpcResponse::Error(format!("{e:#}")),
+            },
+            IpcRequest::ListTimers => IpcResponse::Timers(self.list_timers()),
+            IpcRequest::Timer(id, action) => self.handle_timer_action(&id, action),
+            IpcRequest::Subscribe => {
+                IpcResponse::Error("Subscribing is not supported here".to_string())
+            }
+            IpcRequest::BeginBreak { id, duration } => self.begin_break(id, duration),
+            IpcRequest::EndBreak {
+                id,
+                duration,
+                completed,
+            } => self.end_break(&id, duration, completed),
+            IpcRequest::Pause(duration) => {
+                self.is_enabled = false;
+                self.paused_until = Some(get_unix_time() + duration.as_secs());
+                log::info!("Paused for {}", duration.display());
+                self.record(HistoryEvent::Pause { duration });
+                self.emit(Event::Paused(duration));
+                IpcResponse::Ok
+            }
+        })
+    }
+}
+
+/// The blinkctl binary next to the daemon, or from the PATH
+fn blinkctl_command() -> String {
+    std::env::current_exe()
+        .map(|exe| exe.with_file_name("blinkctl"))
+        .ok()
+        .filter(|path| path.exists())
+        .map_or("blinkctl".to_string(), |path| path.display().to_string())
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+
+/// A daemon that does not save its state, with the timers scheduled
+#[cfg(test)]
+fn test_daemon(config: &str) -> Daemon {
+    let config: Config = serde_yaml_ng::from_str(config).unwrap();
+    let mut daemon = Daemon::new(config, PathBuf::new());
+    daemon.state_path = None;
+    daemon.history = None;
+    daemon.activity_connected = daemon.config.input_tracking.is_some();
+    daemon.update_timer();
+    daemon
+}
+
+/// Runs the ticks of the given duration at once
+#[cfg(test)]
+fn advance(daemon: &mut Daemon, duration: Duration) {
+    for _ in 0..duration.as_secs() {
+        let now = daemon.last_update + TICK_INTERVAL;
+        daemon.tick(now);
+    }
+}
+
+#[cfg(test)]
+const BREAK_CONFIG: &str = "
+timers:
+- interval: 20:00
+  break_duration: 01:00
+input_tracking:
+  pause_after: 10:00
+  reset_after: 30:00
+";
+
+#[test]
+fn break_test() {
+    let mut daemon = test_daemon(BREAK_CONFIG);
+    advance(&mut daemon, Duration::from_secs(20 * 60));
+    assert_eq!(daemon.timers[0].prompts, 1);
+    assert!(daemon.on_break.is_some());
+
+    // The break is only taken once there was no input for its duration
+    advance(&mut daemon, Duration::from_secs(30));
+    assert!(daemon.on_break.is_some());
+    daemon.last_input = get_unix_time_ms() - 60_000;
+    advance(&mut daemon, TICK_INTERVAL);
+    assert!(daemon.on_break.is_none());
+    assert_eq!(daemon.timers[0].prompts, 0);
+    assert_eq!(daemon.timers[0].reset_at, Duration::from_secs(20 * 60 + 30));
+    assert_eq!(
+        daemon.next_timer_at - daemon.elapsed,
+        Duration::from_secs(20 * 60) - TICK_INTERVAL
+    );
+}
+
+#[test]
+fn break_reset_test() {
+    let mut daemon = test_daemon(BREAK_CONFIG);
+    advance(&mut daemon, Duration::from_secs(20 * 60));
+    assert!(daemon.on_break.is_some());
+
+    // Inactivity resets all timers and ends the break, without taking it
+    daemon.last_input = get_unix_time_ms() - 30 * 60_000;
+    advance(&mut daemon, TICK_INTERVAL);
+    assert!(daemon.on_break.is_none());
+    assert!(daemon.is_frozen);
+    assert_eq!(daemon.elapsed, Duration::ZERO);
+    assert_eq!(daemon.timers[0].prompts, 0);
+    assert_eq!(daemon.next_timer_at, Duration::from_secs(20 * 60));
+}
+
+#[test]
+fn break_screen_test() {
+    let mut daemon = test_daemon(BREAK_CONFIG);
+    advance(&mut daemon, Duration::from_secs(20 * 60));
+    assert!(matches!(
+        daemon.begin_break(None, None),
+        IpcResponse::Break(_)
+    ));
+
+    // The break screen ends the break, it stays due when the screen is aborted
+    daemon.last_input = get_unix_time_ms() - 60_000;
+    advance(&mut daemon, TICK_INTERVAL);
+    assert!(daemon.on_break.is_some());
+    daemon.end_break("20:00", Duration::from_secs(10), false);
+    advance(&mut daemon, TICK_INTERVAL);
+    assert!(daemon.on_break.is_none());
+    assert_eq!(daemon.timers[0].prompts, 0);
+
+    // A break screen that is closed without ending the break no longer blocks it
+    daemon.last_input = get_unix_time_ms();
+    advance(&mut daemon, Duration::from_secs(20 * 60 + 1));
+    assert!(daemon.on_break.is_some());
+    daemon.begin_break(None, None);
+    daemon.last_input = get_unix_time_ms() - 60_000;
+    advance(&mut daemon, Duration::from_secs(60));
+    assert!(daemon.on_break.is_some());
+    advance(&mut daemon, BREAK_SCREEN_GRACE);
+    assert!(daemon.on_break.is_none());
+}
+
+#[test]
+fn pause_test() {
+    let mut daemon = test_daemon("timers: [{ interval: 20:00 }]");
+    advance(&mut daemon, Duration::from_secs(60));
+    daemon
+        .handle_msg(IpcRequest::Pause(Duration::from_secs(60)))
+        .unwrap();
+    advance(&mut daemon, Duration::from_secs(60));
+    assert!(!daemon.is_enabled);
+    assert_eq!(daemon.elapsed, Duration::from_secs(60));
+
+    daemon.paused_until = Some(get_unix_time() - 1);
+    advance(&mut daemon, TICK_INTERVAL);
+    assert!(daemon.is_enabled);
+    assert_eq!(daemon.paused_until, None);
+    assert_eq!(daemon.elapsed, Duration::from_secs(61));
+}
+
+#[test]
+fn reload_test() {
+    let mut daemon = test_daemon("timers: [{ interval: 20:00 }, { interval: 01:00:00 }]");
+    advance(&mut daemon, Duration::from_secs(25 * 60));
+    assert_eq!(daemon.timers[0].prompts, 1);
+
+    // The progress of the unchanged timer is kept
+    let path = std::env::temp_dir().join(format!("blink-reload-{}.yaml", std::process::id()));
+    std::fs::write(&path, "timers: [{ interval: 20:00 }, { interval: 45:00 }]").unwrap();
+    daemon.config_path = path.clone();
+    let result = daemon.reload();
+    std::fs::remove_file(path).unwrap();
+    result.unwrap();
+    assert_eq!(daemon.timers[0].prompts, 1);
+    assert_eq!(daemon.next_timer, Some(0));
+    assert_eq!(daemon.next_timer_at, Duration::from_secs(40 * 60));
+    assert_eq!(
+        daemon.timers[1].timer.interval,
+        Duration::from_secs(45 * 60)
+    );
+}
+
+#[test]
+fn reload_input_tracking_test() {
+    let mut daemon = test_daemon("timers: [{ interval: 20:00 }]");
+    let path = std::env::temp_dir().join(format!("blink-reload-input-{}.yaml", std::process::id()));
+    std::fs::write(
+        &path,
+        "
+timers:
+- interval: 20:00
+- typing: { keystrokes: 1000 }
+input_tracking:
+  pause_after: 10:00
+  reset_after: 30:00
+",
+    )
+    .unwrap();
+    daemon.config_path = path.clone();
+    let result = daemon.reload();
+    std::fs::remove_file(path).unwrap();
+    result.unwrap();
+    // Input tracking is only enabled after a restart, so the typing timer is left out
+    assert!(daemon.config.input_tracking.is_none());
+    assert_eq!(daemon.timers.len(), 1);
+}
+
+#[test]
+fn snooze_test() {
+    let mut daemon = test_daemon(
+        "
+timers:
+- interval: 20:00
+  break_duration: 01:00
+- interval: 30:00
+input_tracking:
+  pause_after: 10:00
+  reset_after: 30:00
+",
+    );
+    advance(&mut daemon, Duration::from_secs(20 * 60));
+    assert_eq!(daemon.next_timer, Some(1));
+
+    // The break that is due is snoozed, instead of the next timer
+    assert!(matches!(
+        daemon
+            .handle_msg(IpcRequest::Snooze(Duration::from_secs(5 * 60)))
+            .unwrap(),
+        IpcResponse::Ok
+    ));
+    assert_eq!(daemon.next_timer, Some(0));
+    assert_eq!(daemon.next_timer_at, Duration::from_secs(25 * 60));
+
+    // Changes of the other timers do not drop the snooze
+    assert!(matches!(
+        daemon.handle_timer_action("30:00", TimerAction::Reset),
+        IpcResponse::Ok
+    ));
+    assert_eq!(daemon.next_timer, Some(0));
+    assert_eq!(daemon.next_timer_at, Duration::from_secs(25 * 60));
+
+    advance(&mut daemon, Duration::from_secs(5 * 60));
+    assert_eq!(daemon.timers[0].prompts, 2);
+    assert_eq!(daemon.timers[0].snoozed_until, None);
+    assert_eq!(daemon.next_timer_at, Duration::from_secs(40 * 60));
+}
+
+#[test]
+fn snooze_due_test() {
+    let mut daemon = test_daemon("timers: [{ interval: 20:00 }]");
+    advance(&mut daemon, Duration::from_secs(60));
+
+    // A snooze never makes a timer fire earlier
+    daemon.snooze(Duration::from_secs(5 * 60));
+    assert_eq!(daemon.next_timer_at, Duration::from_secs(20 * 60));
+    daemon.snooze(Duration::from_secs(30 * 60));
+    assert_eq!(daemon.next_timer_at, Duration::from_secs(31 * 60));
+    assert_eq!(daemon.timers[0].snoozes, 2);
+}
+
+#[test]
+fn suppress_test() {
+    let mut daemon = test_daemon(
+        "
+timers:
+- interval: 20:00
+- interval: 01:00:00
+  decline: 1.0
+  satisfies: [20:00]
+  suppress_window: 10:00
+",
+    );
+    advance(&mut daemon, Duration::from_secs(60 * 60));
+    assert_eq!(daemon.timers[1].prompts, 1);
+    assert_eq!(daemon.timers[0].prompts, 0);
+
+    // With the decline, the hourly timer fires 10 minutes after the other timer and suppresses it
+    assert_eq!(daemon.next_timer, Some(1));
+    assert_eq!(daemon.next_timer_at, Duration::from_secs(90 * 60));
+    advance(&mut daemon, Duration::from_secs(30 * 60));
+    assert_eq!(daemon.timers[1].prompts, 2);
+    assert_eq!(daemon.timers[0].prompts, 0);
+}
+
+/// Restores a state that was saved some seconds ago with 10 minutes elapsed and one prompt
+#[cfg(test)]
+fn restore_test_daemon(config: &str, name: &str, saved_ago: u64) -> Daemon {
+    let state = State {
+        saved_at: get_unix_time() - saved_ago,
+        elapsed: Duration::from_secs(10 * 60),
+        next_timer_at: Duration::from_secs(30 * 60),
+        next_timer: Some(0),
+        is_enabled: true,
+        paused_until: None,
+        timers: vec![TimerProgress {
+            id: String::from("20:00"),
+            is_enabled: true,
+            prompts: 1,
+            snoozes: 0,
+            snoozed_until: None,
+            reset_at: Duration::ZERO,
+            keystrokes: 0,
+            streak_start: None,
+        }],
+    };
+    let path = std::env::temp_dir().join(format!("blink-{name}-{}.yaml", std::process::id()));
+    state.save(&path).unwrap();
+    let mut daemon = test_daemon(config);
+    daemon.state_path = Some(path.clone());
+    daemon.restore_state();
+    daemon.state_path = None;
+    std::fs::remove_file(path).unwrap();
+    daemon
+}
+
+#[test]
+fn restore_test() {
+    // The time the daemon was not running counts as elapsed
+    let daemon = restore_test_daemon("timers: [{ interval: 20:00 }]", "restore", 60);
+    assert_eq!(daemon.elapsed, Duration::from_secs(11 * 60));
+    assert_eq!(daemon.timers[0].prompts, 1);
+    assert_eq!(daemon.next_timer_at, Duration::from_secs(30 * 60));
+}
+
+#[test]
+fn restore_expired_test() {
+    let daemon = restore_test_daemon("timers: [{ interval: 20:00 }]", "expired", 6 * 60);
+    assert_eq!(daemon.elapsed, Duration::ZERO);
+    assert_eq!(daemon.timers[0].prompts, 0);
+
+    // With input tracking, the state expires after the reset time
+    let daemon = restore_test_daemon(BREAK_CONFIG, "expired-input", 6 * 60);
+    assert_eq!(daemon.timers[0].prompts, 1);
+    let daemon = restore_test_daemon(BREAK_CONFIG, "expired-input", 30 * 60);
+    assert_eq!(daemon.elapsed, Duration::ZERO);
+}
+
+#[test]
+fn restore_frozen_test() {
+    // Being away longer than the pause time would have frozen the timers
+    let daemon = restore_test_daemon(BREAK_CONFIG, "frozen", 11 * 60);
+    assert_eq!(daemon.elapsed, Duration::from_secs(10 * 60));
+    assert_eq!(daemon.timers[0].prompts, 1);
+    let daemon = restore_test_daemon(BREAK_CONFIG, "frozen", 5 * 60);
+    assert_eq!(daemon.elapsed, Duration::from_secs(15 * 60));
+}
+
+#[cfg(test)]
+const TYPING_CONFIG: &str = "
+timers:
+- typing: { keystrokes: 100, streak: 05:00 }
+input_tracking:
+  pause_after: 10:00
+  reset_after: 30:00
+";
+
+/// Sends an activity update with the given number of key presses and time of the last key press
+#[cfg(test)]
+fn type_keys(daemon: &mut Daemon, keystrokes: u64, keyboard: u64) {
+    daemon.update_activity(ActivityMessage {
+        keystrokes,
+        ..ActivityMessage::any(keyboard)
+    });
+    daemon.update_typing();
+}
+
+#[test]
+fn typing_test() {
+    let mut daemon = test_daemon(TYPING_CONFIG);
+    let now = get_unix_time_ms();
+    // The first update is the baseline of the counter
+    type_keys(&mut daemon, 1000, now);
+    type_keys(&mut daemon, 1099, now);
+    assert_eq!(daemon.timers[0].keystrokes, 99);
+    assert_eq!(daemon.timers[0].prompts, 0);
+    type_keys(&mut daemon, 1100, now);
+    assert_eq!(daemon.timers[0].prompts, 1);
+    assert_eq!(daemon.timers[0].keystrokes, 0);
+
+    // A streak fires the timer before the keystrokes are reached
+    daemon.timers[0].streak_start = Some(now - 5 * 60_000);
+    type_keys(&mut daemon, 1110, now);
+    assert_eq!(daemon.timers[0].prompts, 2);
+    assert_eq!(daemon.timers[0].streak_start, None);
+}
+
+#[test]
+fn typing_streak_test() {
+    let mut daemon = test_daemon(TYPING_CONFIG);
+    let now = get_unix_time_ms();
+    type_keys(&mut daemon, 0, now - 60_000);
+    type_keys(&mut daemon, 10, now);
+    assert_eq!(daemon.timers[0].streak_start, Some(now));
+
+    // A micro-pause ends the streak, but keeps the keystrokes
+    type_keys(&mut daemon, 10, now - 10_000);
+    assert_eq!(daemon.timers[0].streak_start, None);
+    assert_eq!(daemon.timers[0].keystrokes, 10);
+}
+
+#[test]
+fn typing_reload_test() {
+    let mut daemon = test_daemon(TYPING_CONFIG);
+    let now = get_unix_time_ms();
+    type_keys(&mut daemon, 0, now);
+    type_keys(&mut daemon, 50, now);
+    let streak_start = daemon.timers[0].streak_start;
+    assert!(streak_start.is_some());
+
+    // The typing progress is kept on a reload
+    let path = std::env::temp_dir().join(format!("blink-typing-{}.yaml", std::process::id()));
+    std::fs::write(&path, TYPING_CONFIG).unwrap();
+    daemon.config_path = path.clone();
+    let result = daemon.reload();
+    std::fs::remove_file(&path).unwrap();
+    result.unwrap();
+    assert_eq!(daemon.timers[0].keystrokes, 50);
+    assert_eq!(daemon.timers[0].streak_start, streak_start);
+
+    // And on a restart
+    daemon.state_path = Some(path.clone());
+    daemon.save_state();
+    let mut restored = test_daemon(TYPING_CONFIG);
+    restored.state_path = Some(path.clone());
+    restored.restore_state();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(restored.timers[0].keystrokes, 50);
+    assert_eq!(restored.timers[0].streak_start, streak_start);
+}
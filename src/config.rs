@@ -1,6 +1,11 @@
-use anyhow::{Context, Result};
+use crate::{InputKind, schedule::Schedule};
+use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf, time::Duration};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -11,13 +16,64 @@ pub struct Config {
     pub input_tracking: Option<InputTracking>,
     /// Whether to show a 'Blink is running' notification at startup
     pub startup_notification: bool,
+    /// Whether to reload the config automatically when the file changes
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub reload_on_change: bool,
+    /// Whether to export the daemon on the D-Bus session bus
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub dbus: bool,
 }
 
 impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let config_str = fs::read_to_string(path).context("failed to read config file")?;
+        let config =
+            serde_yaml_ng::from_str::<Self>(&config_str).context("failed to parse config file")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that the timer ids are unique and that all references to them exist
+    fn validate(&self) -> Result<()> {
+        let ids: Vec<String> = self.timers.iter().map(Timer::id).collect();
+        for (index, id) in ids.iter().enumerate() {
+            if ids[..index].contains(id) {
+                bail!("duplicate timer id '{id}', set a unique id for each timer");
+            }
+        }
+        if let Some(input_tracking) = &self.input_tracking
+            && input_tracking.inputs.is_empty()
+        {
+            bail!("input tracking needs at least one kind of input");
+        }
+        for timer in &self.timers {
+            if let Some(unknown) = timer.satisfies.iter().find(|id| !ids.contains(id)) {
+                bail!("timer '{}' satisfies unknown timer '{unknown}'", timer.id());
+            }
+            match &timer.typing {
+                Some(typing) => {
+                    if typing.keystrokes.is_none() && typing.streak.is_none() {
+                        bail!(
+                            "typing timer '{}' needs a keystroke or streak limit",
+                            timer.id()
+                        );
+                    }
+                    if self.input_tracking.is_none() {
+                        bail!("typing timer '{}' requires input tracking", timer.id());
+                    }
+                }
+                None if timer.interval.is_zero() => {
+                    bail!("timer '{}' needs an interval", timer.id());
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
     pub fn load_or_create(path: PathBuf) -> Result<Self> {
         if path.exists() {
-            let config_str = fs::read_to_string(&path).context("failed to read config file")?;
-            serde_yaml_ng::from_str::<Self>(&config_str).context("failed to parse config file")
+            Self::load(&path)
         } else {
             let default_config = Config::default();
             let config_str = serde_yaml_ng::to_string(&default_config).unwrap();
@@ -38,6 +94,73 @@ pub struct Notification {
     pub descriptions: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeout: Option<u32>,
+    /// Buttons shown on the notification
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<NotificationAction>,
+    /// Duration of the snooze action, defaults to 5 minutes
+    #[serde(with = "duration_format_opt", skip_serializing_if = "Option::is_none")]
+    pub snooze_duration: Option<Duration>,
+}
+
+impl Notification {
+    pub fn snooze_duration(&self) -> Duration {
+        self.snooze_duration
+            .unwrap_or(Duration::from_secs(DEFAULT_SNOOZE_SECS))
+    }
+}
+
+const DEFAULT_SNOOZE_SECS: u64 = 5 * 60;
+
+/// Limits on the typing load after which a timer fires, whichever is reached first
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TypingLimit {
+    /// Number of key presses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keystrokes: Option<u64>,
+    /// Time of continuous typing without a micro-pause
+    #[serde(with = "duration_format_opt", skip_serializing_if = "Option::is_none")]
+    pub streak: Option<Duration>,
+    /// Time without typing that ends a streak, defaults to 10 seconds
+    #[serde(with = "duration_format_opt", skip_serializing_if = "Option::is_none")]
+    pub micro_pause: Option<Duration>,
+}
+
+impl TypingLimit {
+    pub fn micro_pause(&self) -> Duration {
+        self.micro_pause
+            .unwrap_or(Duration::from_secs(DEFAULT_MICRO_PAUSE_SECS))
+    }
+}
+
+const DEFAULT_MICRO_PAUSE_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationAction {
+    /// Postpones the timer by the snooze duration
+    Snooze,
+    /// Skips the break and resets the timer
+    Skip,
+    /// Starts the break now, which resets the timer like a taken break
+    StartBreak,
+}
+
+impl NotificationAction {
+    const ALL: [Self; 3] = [Self::Snooze, Self::Skip, Self::StartBreak];
+
+    /// Identifier of the notification action
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Snooze => "snooze",
+            Self::Skip => "skip",
+            Self::StartBreak => "start_break",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -46,11 +169,45 @@ pub struct InputTracking {
     pub pause_after: Duration,
     #[serde(with = "duration_format")]
     pub reset_after: Duration,
+    /// Where the input activity comes from
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub source: ActivitySource,
+    /// Kinds of input that count as activity
+    #[serde(default = "all_inputs", skip_serializing_if = "is_all_inputs")]
+    pub inputs: Vec<InputKind>,
+}
+
+fn all_inputs() -> Vec<InputKind> {
+    InputKind::ALL.to_vec()
+}
+
+fn is_all_inputs(inputs: &[InputKind]) -> bool {
+    InputKind::ALL.iter().all(|kind| inputs.contains(kind))
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivitySource {
+    /// The actived daemon, which runs as root
+    #[default]
+    Actived,
+    /// The idle hint of the graphical session in systemd-logind
+    Logind,
+    /// The idle time of the X11 screensaver extension
+    X11,
+    /// Input devices read by blinkd itself, requires access to `/dev/input`
+    Evdev,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Timer {
+    /// Unique identifier of the timer, defaults to the interval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Display name of the timer, defaults to the notification title
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     #[serde(with = "duration_format")]
     pub interval: Duration,
     #[serde(
@@ -61,12 +218,61 @@ pub struct Timer {
     pub initial_delay: Option<Duration>,
     #[serde(default, skip_serializing_if = "is_default")]
     pub decline: f64,
+    /// How long the user has to be inactive for the break to count as taken
+    #[serde(
+        default,
+        with = "duration_format_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub break_duration: Option<Duration>,
+    /// Ids of the timers for which a break of this timer counts as well
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub satisfies: Vec<String>,
+    /// Satisfied timers that would fire within this time before this timer are suppressed
+    #[serde(
+        default,
+        with = "duration_format_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub suppress_window: Option<Duration>,
+    /// Maximum number of times the timer can be snoozed, further snoozes are refused until the
+    /// timer is reset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snooze_limit: Option<u32>,
+    /// Days and times in which the timer is active, always active if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub notification: Option<Notification>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sound: Option<PathBuf>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub command: Option<String>,
+    /// Terminal command to show the break screen in, '{}' is replaced by the `blinkctl break` command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub break_screen: Option<String>,
+    /// Fires the timer on the typing load instead of the interval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typing: Option<TypingLimit>,
+}
+
+impl Timer {
+    pub fn id(&self) -> String {
+        self.id.clone().unwrap_or_else(|| {
+            if self.typing.is_some() {
+                String::from("typing")
+            } else {
+                duration_format::to_string(&self.interval)
+            }
+        })
+    }
+
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| self.notification.as_ref().map(|n| n.title.clone()))
+            .unwrap_or_else(|| self.id())
+    }
 }
 
 fn is_default<T: Default + PartialEq>(t: &T) -> bool {
@@ -78,6 +284,7 @@ impl Default for Config {
         Self {
             timers: vec![
                 Timer {
+                    id: Some(String::from("microbreak")),
                     interval: Duration::from_secs(60 * 20),
                     notification: Some(Notification {
                         title: String::from("Microbreak"),
@@ -87,10 +294,12 @@ impl Default for Config {
                             "Stand up and change your posture.".to_string(),
                         ],
                         timeout: Some(10),
+                        ..Default::default()
                     }),
                     ..Default::default()
                 },
                 Timer {
+                    id: Some(String::from("break")),
                     interval: Duration::from_secs(60 * 60),
                     notification: Some(Notification {
                         title: String::from("Take a break!"),
@@ -99,85 +308,107 @@ impl Default for Config {
                                 .to_string(),
                         ],
                         timeout: Some(0), // will never time out
+                        actions: vec![NotificationAction::Snooze, NotificationAction::StartBreak],
+                        ..Default::default()
                     }),
                     decline: 0.5,
+                    break_duration: Some(Duration::from_secs(60 * 5)),
+                    satisfies: vec![String::from("microbreak")],
+                    suppress_window: Some(Duration::from_secs(60 * 5)),
                     ..Default::default()
                 },
             ],
             input_tracking: None, // disabled by default
             startup_notification: true,
+            reload_on_change: false,
+            dbus: false,
+        }
+    }
+}
+
+/// Parses a duration in the format 'mm:ss' or 'hh:mm:ss'
+pub fn parse_duration(str: &str) -> Result<Duration, String> {
+    let parts: Vec<&str> = str.split(':').collect();
+
+    match parts.len() {
+        2 => {
+            // mm:ss format
+            let mins = parts[0]
+                .parse::<u64>()
+                .map_err(|e| format!("failed to parse minutes: {}", e))?;
+            let secs = parts[1]
+                .parse::<u64>()
+                .map_err(|e| format!("failed to parse seconds: {}", e))?;
+
+            if secs > 59 {
+                return Err("seconds must be in range 0-59".to_string());
+            }
+
+            Ok(Duration::from_secs(mins * 60 + secs))
+        }
+        3 => {
+            // hh:mm:ss format
+            let hours = parts[0]
+                .parse::<u64>()
+                .map_err(|e| format!("failed to parse hours: {}", e))?;
+            let mins = parts[1]
+                .parse::<u64>()
+                .map_err(|e| format!("failed to parse minutes: {}", e))?;
+            let secs = parts[2]
+                .parse::<u64>()
+                .map_err(|e| format!("failed to parse seconds: {}", e))?;
+
+            if mins > 59 {
+                return Err("minutes must be in range 0-59".to_string());
+            }
+            if secs > 59 {
+                return Err("seconds must be in range 0-59".to_string());
+            }
+
+            Ok(Duration::from_secs(hours * 3600 + mins * 60 + secs))
         }
+        _ => Err("duration must be in format 'mm:ss' or 'hh:mm:ss'".to_string()),
     }
 }
 
-mod duration_format {
+#[test]
+fn parse_duration_test() {
+    assert_eq!(parse_duration("05:00"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_duration("01:02:03"), Ok(Duration::from_secs(3723)));
+    assert!(parse_duration("00:60").is_err());
+    assert!(parse_duration("5").is_err());
+}
+
+pub(crate) mod duration_format {
     use serde::{Deserialize, Deserializer, Serializer, de::Error};
     use std::time::Duration;
 
-    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
+    pub fn to_string(duration: &Duration) -> String {
         let total_secs = duration.as_secs();
         let hours = total_secs / 3600;
         let mins = (total_secs % 3600) / 60;
         let secs = total_secs % 60;
 
         if hours > 0 {
-            serializer.serialize_str(&format!("{:02}:{:02}:{:02}", hours, mins, secs))
+            format!("{:02}:{:02}:{:02}", hours, mins, secs)
         } else {
-            serializer.serialize_str(&format!("{:02}:{:02}", mins, secs))
+            format!("{:02}:{:02}", mins, secs)
         }
     }
 
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&to_string(duration))
+    }
+
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
     where
         D: Deserializer<'de>,
     {
         let str = String::deserialize(deserializer)?;
-        let parts: Vec<&str> = str.split(':').collect();
-
-        match parts.len() {
-            2 => {
-                // mm:ss format
-                let mins = parts[0]
-                    .parse::<u64>()
-                    .map_err(|e| Error::custom(format!("failed to parse minutes: {}", e)))?;
-                let secs = parts[1]
-                    .parse::<u64>()
-                    .map_err(|e| Error::custom(format!("failed to parse seconds: {}", e)))?;
-
-                if secs > 59 {
-                    return Err(Error::custom("seconds must be in range 0-59"));
-                }
-
-                Ok(Duration::from_secs(mins * 60 + secs))
-            }
-            3 => {
-                // hh:mm:ss format
-                let hours = parts[0]
-                    .parse::<u64>()
-                    .map_err(|e| Error::custom(format!("failed to parse hours: {}", e)))?;
-                let mins = parts[1]
-                    .parse::<u64>()
-                    .map_err(|e| Error::custom(format!("failed to parse minutes: {}", e)))?;
-                let secs = parts[2]
-                    .parse::<u64>()
-                    .map_err(|e| Error::custom(format!("failed to parse seconds: {}", e)))?;
-
-                if mins > 59 {
-                    return Err(Error::custom("minutes must be in range 0-59"));
-                }
-                if secs > 59 {
-                    return Err(Error::custom("seconds must be in range 0-59"));
-                }
-
-                Ok(Duration::from_secs(hours * 3600 + mins * 60 + secs))
-            }
-            _ => Err(Error::custom(
-                "duration must be in format 'mm:ss' or 'hh:mm:ss'",
-            )),
-        }
+        super::parse_duration(&str).map_err(Error::custom)
     }
 }
 
@@ -0,0 +1,242 @@
+use crate::config::duration_format;
+use anyhow::{Context, Result};
+use jiff::{Timestamp, civil::Date, tz::TimeZone};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// A single line in the history file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix time of the event
+    pub time: u64,
+    /// Elapsed time of the current session before the event
+    #[serde(with = "duration_format")]
+    pub elapsed: Duration,
+    #[serde(flatten)]
+    pub event: HistoryEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HistoryEvent {
+    Start,
+    Stop,
+    /// The timers are identified by their id, which stays the same when the interval changes
+    Prompt {
+        id: String,
+    },
+    BreakTaken {
+        id: String,
+        #[serde(with = "duration_format")]
+        duration: Duration,
+    },
+    BreakSkipped {
+        id: String,
+    },
+    /// A break on the break screen was aborted before it was completed
+    BreakAborted {
+        id: String,
+        #[serde(with = "duration_format")]
+        duration: Duration,
+    },
+    Reset {
+        reason: ResetReason,
+    },
+    Toggle {
+        enabled: bool,
+    },
+    Pause {
+        #[serde(with = "duration_format")]
+        duration: Duration,
+    },
+    Resume,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetReason {
+    /// Reset with `blinkctl reset`
+    Manual,
+    /// No input for the configured reset time
+    Inactivity,
+    /// Long delay between ticks, the system was likely suspended
+    Suspend,
+}
+
+/// Append-only log of the daemon events, stored as JSON lines
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).context("failed to create history directory")?;
+        }
+        let mut line = serde_json::to_string(entry).context("failed to serialize entry")?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("failed to open history file")?
+            .write_all(line.as_bytes())
+            .context("failed to write history file")?;
+        Ok(())
+    }
+
+    /// Reads all entries, lines that cannot be parsed are skipped
+    pub fn load(&self) -> Result<Vec<HistoryEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let history_str = fs::read_to_string(&self.path).context("failed to read history file")?;
+        Ok(history_str
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    log::warn!("Skipping invalid history entry: {e}");
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DayStats {
+    /// Number of notifications that were shown
+    pub prompts: u32,
+    /// Number of breaks taken, including resets because of inactivity
+    pub breaks: u32,
+    /// Longest time at the screen without a break, a taken break or a reset ends a session
+    pub longest_session: Duration,
+    /// Total time at the screen
+    pub active: Duration,
+}
+
+/// Groups the history by local date
+pub fn daily_stats(entries: &[HistoryEntry], tz: &TimeZone) -> BTreeMap<Date, DayStats> {
+    let mut stats: BTreeMap<Date, DayStats> = BTreeMap::new();
+    // Elapsed time at the previous entry, from which the active time is counted
+    let mut base = Duration::ZERO;
+    // Elapsed time at the end of the last break, from which the session is counted
+    let mut session_start = Duration::ZERO;
+    for entry in entries {
+        let Ok(timestamp) = Timestamp::from_second(entry.time as i64) else {
+            continue;
+        };
+        let day = stats
+            .entry(timestamp.to_zoned(tz.clone()).date())
+            .or_default();
+        // The elapsed time starts at zero again when the daemon was restarted without its state
+        if entry.elapsed >= base {
+            day.active += entry.elapsed - base;
+        } else {
+            day.active += entry.elapsed;
+            session_start = Duration::ZERO;
+        }
+        day.longest_session = day
+            .longest_session
+            .max(entry.elapsed.saturating_sub(session_start));
+        base = entry.elapsed;
+
+        match entry.event {
+            HistoryEvent::Prompt { .. } => day.prompts += 1,
+            HistoryEvent::BreakTaken { .. } => {
+                day.breaks += 1;
+                session_start = entry.elapsed;
+            }
+            HistoryEvent::Reset { reason } => {
+                if reason != ResetReason::Manual {
+                    day.breaks += 1;
+                }
+                base = Duration::ZERO;
+                session_start = Duration::ZERO;
+            }
+            _ => {}
+        }
+    }
+    stats
+}
+
+#[test]
+fn daily_stats_test() {
+    let entry = |time: u64, elapsed: u64, event: HistoryEvent| HistoryEntry {
+        time,
+        elapsed: Duration::from_secs(elapsed),
+        event,
+    };
+    let id = || String::from("20:00");
+    let entries = vec![
+        entry(0, 0, HistoryEvent::Start),
+        entry(1200, 1200, HistoryEvent::Prompt { id: id() }),
+        entry(
+            1500,
+            1500,
+            HistoryEvent::Reset {
+                reason: ResetReason::Inactivity,
+            },
+        ),
+        entry(2000, 300, HistoryEvent::Stop),
+        // Restarted without restoring the state
+        entry(3000, 0, HistoryEvent::Start),
+        entry(3600, 600, HistoryEvent::Prompt { id: id() }),
+    ];
+    let stats = daily_stats(&entries, &TimeZone::UTC);
+    assert_eq!(
+        stats.get(&jiff::civil::date(1970, 1, 1)),
+        Some(&DayStats {
+            prompts: 2,
+            breaks: 1,
+            longest_session: Duration::from_secs(1500),
+            active: Duration::from_secs(2400),
+        })
+    );
+}
+
+#[test]
+fn daily_stats_break_test() {
+    let entry = |time: u64, elapsed: u64, event: HistoryEvent| HistoryEntry {
+        time,
+        elapsed: Duration::from_secs(elapsed),
+        event,
+    };
+    let id = || String::from("20:00");
+    let entries = vec![
+        entry(0, 0, HistoryEvent::Start),
+        entry(1200, 1200, HistoryEvent::Prompt { id: id() }),
+        // A taken break ends the session, while the elapsed time continues
+        entry(
+            1500,
+            1500,
+            HistoryEvent::BreakTaken {
+                id: id(),
+                duration: Duration::from_secs(60),
+            },
+        ),
+        entry(2400, 2400, HistoryEvent::Prompt { id: id() }),
+        entry(2500, 2500, HistoryEvent::Stop),
+    ];
+    let stats = daily_stats(&entries, &TimeZone::UTC);
+    let day = &stats[&jiff::civil::date(1970, 1, 1)];
+    assert_eq!(day.breaks, 1);
+    assert_eq!(day.longest_session, Duration::from_secs(1500));
+    assert_eq!(day.active, Duration::from_secs(2500));
+}
@@ -0,0 +1,3 @@
+pub mod devices;
+pub mod hotplug;
+pub mod source;
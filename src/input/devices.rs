@@ -0,0 +1,346 @@
+use evdev::{AbsoluteAxisCode, Device, EventType, KeyCode, PropType, RelativeAxisCode};
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, os::unix::fs::MetadataExt, path::Path};
+
+/// Seat of the devices and clients that are not assigned to another seat
+pub const DEFAULT_SEAT: &str = "seat0";
+
+/// Kind of input a device provides, derived from the events it supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Keyboard,
+    Mouse,
+    Touchpad,
+    Touchscreen,
+    Tablet,
+    Joystick,
+    Accelerometer,
+    /// Lid and tablet mode switches
+    Switch,
+    /// Devices with only some keys, e.g. power buttons and media keys
+    Buttons,
+}
+
+/// Devices with these capabilities are tracked when no include rules are configured
+const DEFAULT_CAPABILITIES: [Capability; 5] = [
+    Capability::Keyboard,
+    Capability::Mouse,
+    Capability::Touchpad,
+    Capability::Touchscreen,
+    Capability::Tablet,
+];
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Capability::Keyboard => "keyboard",
+            Capability::Mouse => "mouse",
+            Capability::Touchpad => "touchpad",
+            Capability::Touchscreen => "touchscreen",
+            Capability::Tablet => "tablet",
+            Capability::Joystick => "joystick",
+            Capability::Accelerometer => "accelerometer",
+            Capability::Switch => "switch",
+            Capability::Buttons => "buttons",
+        })
+    }
+}
+
+/// The properties of an input device that are matched against the rules
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub vendor: u16,
+    pub product: u16,
+    pub phys: String,
+    pub capabilities: Vec<Capability>,
+    /// Whether the input is on the screen itself, like on a touchscreen
+    pub direct: bool,
+    pub axes: Vec<AbsAxis>,
+    /// The logind seat the device is assigned to
+    #[serde(default = "default_seat")]
+    pub seat: String,
+}
+
+fn default_seat() -> String {
+    DEFAULT_SEAT.to_string()
+}
+
+/// Range of an absolute axis in device units
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AbsAxis {
+    pub code: u16,
+    pub minimum: i32,
+    pub maximum: i32,
+    /// Changes within the fuzz are filtered by the driver
+    #[serde(default)]
+    pub fuzz: i32,
+}
+
+impl DeviceInfo {
+    pub fn new(path: &Path, device: &Device) -> Self {
+        let id = device.input_id();
+        let axes = device
+            .get_absinfo()
+            .map(|axes| {
+                axes.map(|(code, info)| AbsAxis {
+                    code: code.0,
+                    minimum: info.minimum(),
+                    maximum: info.maximum(),
+                    fuzz: info.fuzz(),
+                })
+                .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            name: device.name().unwrap_or_default().to_string(),
+            vendor: id.vendor(),
+            product: id.product(),
+            phys: device.physical_path().unwrap_or_default().to_string(),
+            capabilities: capabilities(device),
+            direct: device.properties().contains(PropType::DIRECT),
+            axes,
+            seat: device_seat(path).unwrap_or_else(default_seat),
+        }
+    }
+}
+
+/// Reads the seat that udev assigned to a device node from the udev database
+fn device_seat(path: &Path) -> Option<String> {
+    let rdev = fs::metadata(path).ok()?.rdev();
+    let data_path = format!(
+        "/run/udev/data/c{}:{}",
+        libc::major(rdev),
+        libc::minor(rdev)
+    );
+    let data = fs::read_to_string(data_path).ok()?;
+    data.lines()
+        .find_map(|line| line.strip_prefix("E:ID_SEAT="))
+        .map(|seat| seat.to_string())
+}
+
+fn capabilities(device: &Device) -> Vec<Capability> {
+    if device.properties().contains(PropType::ACCELEROMETER) {
+        return vec![Capability::Accelerometer];
+    }
+    let has_key = |key| {
+        device
+            .supported_keys()
+            .is_some_and(|keys| keys.contains(key))
+    };
+    let has_rel = |axis| {
+        device
+            .supported_relative_axes()
+            .is_some_and(|axes| axes.contains(axis))
+    };
+    let has_abs = |axis| {
+        device
+            .supported_absolute_axes()
+            .is_some_and(|axes| axes.contains(axis))
+    };
+
+    let mut capabilities = Vec::new();
+    if has_key(KeyCode::KEY_A) {
+        capabilities.push(Capability::Keyboard);
+    }
+    if has_rel(RelativeAxisCode::REL_X) && has_rel(RelativeAxisCode::REL_Y) {
+        capabilities.push(Capability::Mouse);
+    }
+    // Touchpads and touchscreens also report BTN_TOUCH, so check the more specific tools first
+    if has_key(KeyCode::BTN_TOOL_PEN) {
+        capabilities.push(Capability::Tablet);
+    } else if has_abs(AbsoluteAxisCode::ABS_X) && has_key(KeyCode::BTN_TOOL_FINGER) {
+        capabilities.push(Capability::Touchpad);
+    } else if has_abs(AbsoluteAxisCode::ABS_X) && has_key(KeyCode::BTN_TOUCH) {
+        capabilities.push(Capability::Touchscreen);
+    }
+    if has_key(KeyCode::BTN_SOUTH) || has_key(KeyCode::BTN_TRIGGER) {
+        capabilities.push(Capability::Joystick);
+    }
+    if device.supported_events().contains(EventType::SWITCH) {
+        capabilities.push(Capability::Switch);
+    }
+    if capabilities.is_empty() && device.supported_events().contains(EventType::KEY) {
+        capabilities.push(Capability::Buttons);
+    }
+    capabilities
+}
+
+/// Matches devices on all of the given properties, names and phys paths may contain `*` wildcards
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceRule {
+    pub name: Option<String>,
+    pub vendor: Option<u16>,
+    pub product: Option<u16>,
+    pub phys: Option<String>,
+    pub capability: Option<Capability>,
+}
+
+impl DeviceRule {
+    fn capability(capability: Capability) -> Self {
+        Self {
+            capability: Some(capability),
+            ..Default::default()
+        }
+    }
+
+    pub fn matches(&self, info: &DeviceInfo) -> bool {
+        self.name
+            .as_ref()
+            .is_none_or(|pattern| wildcard_match(pattern, &info.name))
+            && self.vendor.is_none_or(|vendor| vendor == info.vendor)
+            && self.product.is_none_or(|product| product == info.product)
+            && self
+                .phys
+                .as_ref()
+                .is_none_or(|pattern| wildcard_match(pattern, &info.phys))
+            && self
+                .capability
+                .is_none_or(|capability| info.capabilities.contains(&capability))
+    }
+}
+
+impl fmt::Display for DeviceRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(name) = &self.name {
+            parts.push(format!("name '{name}'"));
+        }
+        if let Some(vendor) = self.vendor {
+            parts.push(format!("vendor {vendor:04x}"));
+        }
+        if let Some(product) = self.product {
+            parts.push(format!("product {product:04x}"));
+        }
+        if let Some(phys) = &self.phys {
+            parts.push(format!("phys '{phys}'"));
+        }
+        if let Some(capability) = self.capability {
+            parts.push(format!("capability {capability}"));
+        }
+        if parts.is_empty() {
+            f.write_str("any device")
+        } else {
+            f.write_str(&parts.join(", "))
+        }
+    }
+}
+
+/// Matches a string against a pattern in which `*` matches any sequence of characters
+fn wildcard_match(pattern: &str, str: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == str,
+        Some((prefix, rest)) => {
+            let Some(str) = str.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=str.len())
+                .filter(|i| str.is_char_boundary(*i))
+                .any(|i| wildcard_match(rest, &str[i..]))
+        }
+    }
+}
+
+/// Selects the devices of which the input is tracked
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceFilter {
+    /// A device is tracked if it matches any of these rules
+    pub include: Vec<DeviceRule>,
+    /// A device that matches any of these rules is never tracked
+    pub exclude: Vec<DeviceRule>,
+}
+
+impl Default for DeviceFilter {
+    fn default() -> Self {
+        Self {
+            include: DEFAULT_CAPABILITIES.map(DeviceRule::capability).to_vec(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+/// Whether a device is tracked and the rule that decided it
+pub enum Verdict<'a> {
+    Included(&'a DeviceRule),
+    Excluded(&'a DeviceRule),
+    NotIncluded,
+}
+
+impl Verdict<'_> {
+    pub fn is_tracked(&self) -> bool {
+        matches!(self, Verdict::Included(_))
+    }
+}
+
+impl fmt::Display for Verdict<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Included(rule) => write!(f, "tracked, included by rule ({rule})"),
+            Verdict::Excluded(rule) => write!(f, "ignored, excluded by rule ({rule})"),
+            Verdict::NotIncluded => write!(f, "ignored, no include rule matches"),
+        }
+    }
+}
+
+impl DeviceFilter {
+    pub fn check(&self, info: &DeviceInfo) -> Verdict<'_> {
+        let Some(include) = self.include.iter().find(|rule| rule.matches(info)) else {
+            return Verdict::NotIncluded;
+        };
+        match self.exclude.iter().find(|rule| rule.matches(info)) {
+            Some(exclude) => Verdict::Excluded(exclude),
+            None => Verdict::Included(include),
+        }
+    }
+}
+
+#[test]
+fn device_filter_test() {
+    let filter: DeviceFilter = serde_yaml_ng::from_str(
+        "
+include:
+  - capability: keyboard
+  - capability: touchscreen
+exclude:
+  - name: '*Accelerometer*'
+  - vendor: 0x045e
+    product: 0x028e
+  - phys: 'usb-0000:00:14.0-2/*'
+",
+    )
+    .unwrap();
+    let keyboard = DeviceInfo {
+        name: "AT Translated Set 2 keyboard".to_string(),
+        vendor: 0x0001,
+        product: 0x0001,
+        phys: "isa0060/serio0/input0".to_string(),
+        capabilities: vec![Capability::Keyboard],
+        ..Default::default()
+    };
+    assert!(filter.check(&keyboard).is_tracked());
+    let controller = DeviceInfo {
+        vendor: 0x045e,
+        product: 0x028e,
+        ..keyboard.clone()
+    };
+    assert!(!filter.check(&controller).is_tracked());
+    let external = DeviceInfo {
+        phys: "usb-0000:00:14.0-2/input0".to_string(),
+        ..keyboard.clone()
+    };
+    assert!(!filter.check(&external).is_tracked());
+    let mouse = DeviceInfo {
+        capabilities: vec![Capability::Mouse],
+        ..keyboard
+    };
+    assert!(!filter.check(&mouse).is_tracked());
+    assert!(DeviceFilter::default().check(&mouse).is_tracked());
+
+    assert!(wildcard_match("*Mouse*", "Logitech USB Mouse"));
+    assert!(wildcard_match("a*b*c", "abc"));
+    assert!(!wildcard_match("a*b", "abc"));
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    ffi::CString,
+    io,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
+};
+use tokio::io::unix::AsyncFd;
+
+/// Watches a directory for added, removed and changed files using inotify
+pub struct DirWatcher {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl DirWatcher {
+    pub fn new(path: &Path) -> Result<Self> {
+        // SAFETY: inotify_init1 has no preconditions, the fd is owned from here on
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error()).context("failed to initialize inotify");
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let c_path = CString::new(path.as_os_str().as_bytes()).context("invalid path")?;
+        // Device nodes are created by the kernel and get their permissions from udev afterwards
+        let mask = libc::IN_CREATE | libc::IN_DELETE | libc::IN_ATTRIB;
+        // SAFETY: the fd is a valid inotify instance and the path a valid C string
+        let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), c_path.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("failed to watch '{}'", path.display()));
+        }
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+        })
+    }
+
+    /// Waits until something in the directory changed, the details of the change are discarded
+    pub async fn changed(&mut self) -> Result<()> {
+        let mut buf = [0u8; 4096];
+        loop {
+            let mut guard = self.fd.readable().await?;
+            let result = guard.try_io(|fd| {
+                // SAFETY: the buffer is valid for writes of its length
+                let len = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+                if len < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(len)
+                }
+            });
+            match result {
+                Ok(Ok(0)) => bail!("inotify was closed"),
+                Ok(Ok(_)) => return Ok(()),
+                Ok(Err(e)) => return Err(e).context("failed to read inotify events"),
+                // Spurious wakeup, the readiness was cleared
+                Err(_) => continue,
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use super::{devices::DeviceInfo, hotplug::DirWatcher};
+use crate::get_unix_time_ms;
+use anyhow::{Context, Result};
+use evdev::{Device, InputEvent};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::mpsc,
+    task::{self, JoinSet},
+};
+
+const INPUT_DIR: &str = "/dev/input";
+// Time between a change in the input directory and scanning it for devices
+const SETTLE_DELAY: Duration = Duration::from_millis(200);
+
+pub enum SourceEvent {
+    /// A device was found, its input is only reported once it is tracked
+    Added { path: PathBuf, info: DeviceInfo },
+    /// A device was removed or can no longer be read
+    Removed { path: PathBuf },
+    /// All devices that are present have been reported
+    Scanned,
+    /// Input of a tracked device, at a unix time in ms
+    Input {
+        path: PathBuf,
+        event: InputEvent,
+        time: u64,
+        /// Monotonic time in ms since the source started, for the intervals between events
+        uptime: u64,
+    },
+}
+
+/// Provides the input devices and their events
+pub trait InputSource {
+    /// Waits for the next change of the devices or input event, `None` when the source has ended
+    fn next(&mut self) -> impl Future<Output = Result<Option<SourceEvent>>> + Send;
+
+    /// Starts reporting the input of an added device
+    fn track(&mut self, path: &Path) -> Result<()>;
+}
+
+/// The input devices in /dev/input, which are rescanned when devices are plugged in or removed
+pub struct EvdevSource {
+    watcher: DirWatcher,
+    /// Devices that were found but are not tracked
+    found: HashMap<PathBuf, Device>,
+    /// Tracked devices by the id of the task that listens on them
+    tracked: HashMap<task::Id, PathBuf>,
+    listeners: JoinSet<()>,
+    event_tx: mpsc::UnboundedSender<(PathBuf, InputEvent, u64, u64)>,
+    event_rx: mpsc::UnboundedReceiver<(PathBuf, InputEvent, u64, u64)>,
+    pending: VecDeque<SourceEvent>,
+    started: Instant,
+}
+
+impl EvdevSource {
+    pub fn new() -> Result<Self> {
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let mut source = Self {
+            watcher: DirWatcher::new(Path::new(INPUT_DIR))?,
+            found: HashMap::new(),
+            tracked: HashMap::new(),
+            listeners: JoinSet::new(),
+            event_tx,
+            event_rx,
+            pending: VecDeque::new(),
+            started: Instant::now(),
+        };
+        source.scan();
+        Ok(source)
+    }
+
+    fn scan(&mut self) {
+        let mut present = HashSet::new();
+        for (path, device) in evdev::enumerate() {
+            present.insert(path.clone());
+            if self.tracked.values().any(|tracked| *tracked == path)
+                || self.found.contains_key(&path)
+            {
+                continue;
+            }
+            let info = DeviceInfo::new(&path, &device);
+            self.pending.push_back(SourceEvent::Added {
+                path: path.clone(),
+                info,
+            });
+            self.found.insert(path, device);
+        }
+        // Tracked devices are removed when their listener stops
+        self.found.retain(|path, _| {
+            let is_present = present.contains(path);
+            if !is_present {
+                self.pending
+                    .push_back(SourceEvent::Removed { path: path.clone() });
+            }
+            is_present
+        });
+        self.pending.push_back(SourceEvent::Scanned);
+    }
+}
+
+impl InputSource for EvdevSource {
+    async fn next(&mut self) -> Result<Option<SourceEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            tokio::select! {
+                result = self.watcher.changed() => {
+                    result?;
+                    // Give udev some time to set up the device
+                    tokio::time::sleep(SETTLE_DELAY).await;
+                    self.scan();
+                }
+                Some((path, event, time, uptime)) = self.event_rx.recv() => {
+                    return Ok(Some(SourceEvent::Input { path, event, time, uptime }));
+                }
+                Some(result) = self.listeners.join_next_with_id() => {
+                    let id = match result {
+                        Ok((id, ())) => id,
+                        Err(e) => {
+                            log::error!("Listener stopped unexpectedly: {e}");
+                            e.id()
+                        }
+                    };
+                    if let Some(path) = self.tracked.remove(&id) {
+                        return Ok(Some(SourceEvent::Removed { path }));
+                    }
+                }
+            }
+        }
+    }
+
+    fn track(&mut self, path: &Path) -> Result<()> {
+        let device = self
+            .found
+            .remove(path)
+            .with_context(|| format!("device '{}' was not found", path.display()))?;
+        let mut events = device
+            .into_event_stream()
+            .context("Failed to create event stream")?;
+        let path = path.to_path_buf();
+        let event_tx = self.event_tx.clone();
+        let started = self.started;
+        let listener = self.listeners.spawn({
+            let path = path.clone();
+            async move {
+                loop {
+                    match events.next_event().await {
+                        Ok(event) => {
+                            let uptime = started.elapsed().as_millis() as u64;
+                            let _ =
+                                event_tx.send((path.clone(), event, get_unix_time_ms(), uptime));
+                        }
+                        Err(e) => {
+                            log::info!("Stopped listening on '{}': {e}", path.display());
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        // Removed when the listener stops for any reason, also when it panics
+        self.tracked.insert(listener.id(), path);
+        Ok(())
+    }
+}
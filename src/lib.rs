@@ -1,7 +1,13 @@
 pub mod async_socket;
 pub mod config;
+pub mod history;
+pub mod input;
+pub mod schedule;
+pub mod state;
 pub mod util;
 
+use evdev::{EventType, KeyCode, RelativeAxisCode};
+use history::ResetReason;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{self, Display, Write},
@@ -27,6 +33,21 @@ pub fn actived_socket_path() -> PathBuf {
         .with_extension("sock")
 }
 
+pub fn actived_config_path() -> PathBuf {
+    PathBuf::from("/etc")
+        .join(APP_NAME)
+        .join(ACTIVED_NAME)
+        .with_extension("yaml")
+}
+
+pub fn state_path() -> Option<PathBuf> {
+    dirs::state_dir().map(|dir| dir.join(APP_NAME).join("state.yaml"))
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_NAME).join("history.jsonl"))
+}
+
 pub fn get_unix_time() -> u64 {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -34,9 +55,145 @@ pub fn get_unix_time() -> u64 {
         .as_secs()
 }
 
+pub fn get_unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// Kind of input that is tracked separately by the activity daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputKind {
+    Keyboard,
+    /// Mice, touchpads and tablets
+    Pointer,
+    Touch,
+}
+
+impl InputKind {
+    pub const ALL: [InputKind; 3] = [InputKind::Keyboard, InputKind::Pointer, InputKind::Touch];
+
+    /// Kind of an input event, `direct` is set for devices on a screen like touchscreens. The
+    /// buttons from BTN_0 up to KEY_OK belong to mice, touchpads, tablets and joysticks
+    pub fn of_event(event: &evdev::InputEvent, direct: bool) -> Option<Self> {
+        let is_button = (KeyCode::BTN_0.code()..KeyCode::KEY_OK.code()).contains(&event.code());
+        match event.event_type() {
+            EventType::KEY if !is_button => Some(InputKind::Keyboard),
+            EventType::KEY | EventType::ABSOLUTE if direct => Some(InputKind::Touch),
+            EventType::KEY | EventType::RELATIVE | EventType::ABSOLUTE => Some(InputKind::Pointer),
+            _ => None,
+        }
+    }
+}
+
+/// Version of the actived protocol, incremented on incompatible changes. Version 1 had no
+/// handshake and only sent the time of the last input
+pub const ACTIVED_PROTOCOL_VERSION: u32 = 2;
+
+/// Optional features of the actived protocol
+pub mod actived_features {
+    /// Separate times for keyboard, pointer and touch input
+    pub const INPUT_KINDS: &str = "input_kinds";
+    /// Counters of the key presses and pointer movement
+    pub const INPUT_COUNTS: &str = "input_counts";
+
+    pub const ALL: [&str; 2] = [INPUT_KINDS, INPUT_COUNTS];
+}
+
+/// First message on an actived connection, sent by the client and answered by actived
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivedHello {
+    pub version: u32,
+    /// Features of the sender, unknown features are ignored
+    pub features: Vec<String>,
+}
+
+impl ActivedHello {
+    /// The hello of this version
+    pub fn current() -> Self {
+        Self {
+            version: ACTIVED_PROTOCOL_VERSION,
+            features: actived_features::ALL.map(String::from).to_vec(),
+        }
+    }
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+/// Unix times in milliseconds of the last input, the times per kind are zero when there was no
+/// such input
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct ActivityMessage {
+    /// Incremented for every update, a gap means that updates were dropped
+    pub seq: u64,
     pub last_input: u64,
+    pub keyboard: u64,
+    pub pointer: u64,
+    pub touch: u64,
+    /// Number of key presses since the activity daemon started, not which keys were pressed
+    pub keystrokes: u64,
+    /// Relative pointer movement since the activity daemon started, in device units
+    pub pointer_motion: u64,
+}
+
+impl ActivityMessage {
+    /// Activity of a source that cannot tell the kinds of input apart
+    pub fn any(last_input: u64) -> Self {
+        Self {
+            last_input,
+            keyboard: last_input,
+            pointer: last_input,
+            touch: last_input,
+            ..Default::default()
+        }
+    }
+
+    pub fn get(&self, kind: InputKind) -> u64 {
+        match kind {
+            InputKind::Keyboard => self.keyboard,
+            InputKind::Pointer => self.pointer,
+            InputKind::Touch => self.touch,
+        }
+    }
+
+    pub fn record(&mut self, kind: InputKind, time: u64) {
+        match kind {
+            InputKind::Keyboard => self.keyboard = time,
+            InputKind::Pointer => self.pointer = time,
+            InputKind::Touch => self.touch = time,
+        }
+        self.last_input = self.last_input.max(time);
+    }
+
+    /// Adds the key presses and pointer movement of an event to the counters
+    pub fn count(&mut self, event: &evdev::InputEvent, kind: InputKind) {
+        match event.event_type() {
+            // A value of 1 is a press, 0 a release and 2 a repeat
+            EventType::KEY if kind == InputKind::Keyboard && event.value() == 1 => {
+                self.keystrokes += 1;
+            }
+            EventType::RELATIVE
+                if event.code() == RelativeAxisCode::REL_X.0
+                    || event.code() == RelativeAxisCode::REL_Y.0 =>
+            {
+                self.pointer_motion += event.value().unsigned_abs() as u64;
+            }
+            _ => {}
+        }
+    }
+
+    /// Time of the last input of the given kinds, `None` if there was no such input
+    pub fn last_input_of(&self, kinds: &[InputKind]) -> Option<u64> {
+        kinds
+            .iter()
+            .map(|kind| self.get(*kind))
+            .filter(|time| *time != 0)
+            .max()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,38 +201,368 @@ pub enum IpcRequest {
     Status,
     Toggle,
     Reset,
+    Snooze(Duration),
+    Pause(Duration),
+    Reload,
+    ListTimers,
+    Timer(String, TimerAction),
+    /// Keeps the connection open and streams `IpcResponse::Event`s
+    Subscribe,
+    /// Starts a break on the break screen, for the current break or the next timer if no id is
+    /// given, the duration defaults to the break duration of the timer
+    BeginBreak {
+        id: Option<String>,
+        duration: Option<Duration>,
+    },
+    /// Ends a break that was started with `BeginBreak`
+    EndBreak {
+        id: String,
+        duration: Duration,
+        completed: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum TimerAction {
+    Enable,
+    Disable,
+    Reset,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IpcResponse {
     Ok,
     Status(Status),
+    Timers(Vec<TimerStatus>),
+    Event(Event),
+    Break(BreakInfo),
+    Error(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakInfo {
+    pub id: String,
+    pub name: String,
+    pub duration: Duration,
+    /// Description of the last notification of the timer
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimerStatus {
+    pub id: String,
+    pub name: String,
+    pub time_left: Duration,
+    pub prompts: u64,
+    pub is_enabled: bool,
+    /// Whether the timer is within its schedule
+    pub is_active: bool,
+    /// Whether this timer fires next
+    pub is_next: bool,
+    /// Progress of a timer that fires on the typing load instead of the elapsed time
+    #[serde(default)]
+    pub typing: Option<TypingProgress>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypingProgress {
+    /// Key presses since the timer was reset
+    pub keystrokes: u64,
+    pub keystroke_limit: Option<u64>,
+    /// Time of typing without a micro-pause
+    pub streak: Duration,
+    pub streak_limit: Option<Duration>,
+}
+
+impl Display for TypingProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(limit) = self.keystroke_limit {
+            write!(f, "{}/{limit} keys", self.keystrokes)?;
+        }
+        if let Some(limit) = self.streak_limit {
+            if self.keystroke_limit.is_some() {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}/{}", self.streak.display(), limit.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Status {
-    elapsed: Duration,
-    next_timer: Duration,
+    /// Time at the screen since the last reset
+    pub elapsed: Duration,
+    /// Elapsed time at which the next timer fires, `Duration::MAX` if there is none
+    pub next_timer: Duration,
+    pub next_timer_id: Option<String>,
+    pub next_timer_name: Option<String>,
+    pub is_enabled: bool,
+    /// Whether the timers are frozen because of input inactivity
+    pub is_frozen: bool,
+    /// Time since the last input, only available with input tracking
+    pub idle: Option<Duration>,
+    pub on_break: Option<BreakStatus>,
+    /// Time until the timers resume after a pause
+    pub paused: Option<Duration>,
+    /// Time until a timer that is outside of its schedule becomes active
+    pub next_active: Option<Duration>,
+    /// Key presses in the last minute, only available with an activity source that counts them
+    pub typing_rate: Option<u64>,
+    /// Pointer movement in the last minute, in device units
+    pub pointer_rate: Option<u64>,
+    /// Connection with the activity source, only available with input tracking
+    pub activity: Option<ActivityState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityState {
+    Connected,
+    /// Not connected yet or lost, blinkd keeps trying to reconnect
+    Disconnected,
+}
+
+impl Display for ActivityState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ActivityState::Connected => "connected",
+            ActivityState::Disconnected => "disconnected",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakStatus {
+    pub remaining: Duration,
+    pub duration: Duration,
 }
 
 impl Status {
-    pub fn new(elapsed: Duration, next_timer: Duration) -> Self {
-        Self {
-            elapsed,
-            next_timer,
+    /// Time until the next timer fires
+    pub fn remaining(&self) -> Option<Duration> {
+        (self.next_timer != Duration::MAX).then(|| self.next_timer.saturating_sub(self.elapsed))
+    }
+
+    /// A single word describing the state of the daemon
+    pub fn state(&self) -> &'static str {
+        if self.paused.is_some() {
+            "paused"
+        } else if !self.is_enabled {
+            "disabled"
+        } else if self.on_break.is_some() {
+            "break"
+        } else if self.is_frozen {
+            "frozen"
+        } else {
+            "active"
         }
     }
+
+    /// Replaces the placeholders like `{remaining}` in a template, unavailable values are left empty
+    pub fn format(&self, template: &str) -> String {
+        let duration = |d: Option<Duration>| d.map(|d| d.display().to_string()).unwrap_or_default();
+        let placeholders = [
+            ("state", self.state().to_string()),
+            ("elapsed", self.elapsed.display().to_string()),
+            ("remaining", duration(self.remaining())),
+            ("id", self.next_timer_id.clone().unwrap_or_default()),
+            ("name", self.next_timer_name.clone().unwrap_or_default()),
+            ("idle", duration(self.idle)),
+            (
+                "break_remaining",
+                duration(self.on_break.as_ref().map(|b| b.remaining)),
+            ),
+            (
+                "break_duration",
+                duration(self.on_break.as_ref().map(|b| b.duration)),
+            ),
+            ("pause_remaining", duration(self.paused)),
+            ("next_active", duration(self.next_active)),
+            ("enabled", self.is_enabled.to_string()),
+            ("paused", self.paused.is_some().to_string()),
+            ("frozen", self.is_frozen.to_string()),
+            (
+                "typing_rate",
+                self.typing_rate.map(|r| r.to_string()).unwrap_or_default(),
+            ),
+            (
+                "pointer_rate",
+                self.pointer_rate.map(|r| r.to_string()).unwrap_or_default(),
+            ),
+            (
+                "activity",
+                self.activity.map(|a| a.to_string()).unwrap_or_default(),
+            ),
+        ];
+        let mut result = template.to_string();
+        for (key, value) in placeholders {
+            result = result.replace(&format!("{{{key}}}"), &value);
+        }
+        result
+    }
+
+    /// JSON representation for scripts, durations are in seconds
+    pub fn to_json(&self) -> serde_json::Value {
+        let secs = |d: Option<Duration>| d.map(|d| d.as_secs());
+        serde_json::json!({
+            "state": self.state(),
+            "enabled": self.is_enabled,
+            "paused": self.paused.is_some(),
+            "frozen": self.is_frozen,
+            "on_break": self.on_break.is_some(),
+            "elapsed": self.elapsed.as_secs(),
+            "remaining": secs(self.remaining()),
+            "next_timer": {
+                "id": self.next_timer_id,
+                "name": self.next_timer_name,
+            },
+            "idle": secs(self.idle),
+            "break_remaining": secs(self.on_break.as_ref().map(|b| b.remaining)),
+            "break_duration": secs(self.on_break.as_ref().map(|b| b.duration)),
+            "pause_remaining": secs(self.paused),
+            "next_active": secs(self.next_active),
+            "typing_rate": self.typing_rate,
+            "pointer_rate": self.pointer_rate,
+            "activity": self.activity,
+        })
+    }
+}
+
+#[test]
+fn status_format_test() {
+    let status = Status {
+        elapsed: Duration::from_secs(90),
+        next_timer: Duration::from_secs(1200),
+        next_timer_name: Some("Microbreak".to_string()),
+        is_enabled: true,
+        typing_rate: Some(120),
+        ..Default::default()
+    };
+    assert_eq!(
+        status.format("{name} in {remaining} ({state}){idle}"),
+        "Microbreak in 18:30 (active)"
+    );
+    assert_eq!(
+        status.format("{typing_rate} keys, {pointer_rate} moved"),
+        "120 keys,  moved"
+    );
+    assert_eq!(status.to_json()["remaining"], 1110);
 }
 
 impl Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         format_duration(f, self.elapsed)?;
         f.write_char('/')?;
-        format_duration(f, self.next_timer)?;
+        if self.next_timer == Duration::MAX {
+            // There is no active timer
+            f.write_str("--:--")?;
+        } else {
+            format_duration(f, self.next_timer)?;
+        }
+        if !self.is_enabled && self.paused.is_none() {
+            f.write_str(" (disabled)")?;
+        }
+        if self.activity == Some(ActivityState::Disconnected) {
+            f.write_str(" (no activity source)")?;
+        }
+        if let Some(on_break) = &self.on_break {
+            f.write_str(" (on break, ")?;
+            format_duration(f, on_break.remaining)?;
+            f.write_str(" of ")?;
+            format_duration(f, on_break.duration)?;
+            f.write_str(" remaining)")?;
+        }
+        if let Some(remaining) = self.paused {
+            f.write_str(" (paused, ")?;
+            format_duration(f, remaining)?;
+            f.write_str(" remaining)")?;
+        }
+        if let Some(next_active) = self.next_active {
+            f.write_str(" (next timer active in ")?;
+            format_duration(f, next_active)?;
+            f.write_char(')')?;
+        }
         Ok(())
     }
 }
 
+/// Events that are sent to subscribed clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Event {
+    /// The status after every tick of the daemon
+    Status(Status),
+    TimerFired {
+        id: String,
+        name: String,
+    },
+    Paused(Duration),
+    Resumed,
+    Toggled {
+        enabled: bool,
+    },
+    Reset(ResetReason),
+    /// The timers are frozen because there was no input
+    Frozen,
+    Unfrozen,
+    /// The connection with the activity source was lost, input tracking is suspended
+    ActivityLost,
+    /// The activity source is connected again after it was lost
+    ActivityRestored,
+}
+
+impl Event {
+    /// JSON representation for scripts, durations are in seconds
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Event::Status(status) => serde_json::json!({
+                "event": "status",
+                "status": status.to_json(),
+            }),
+            Event::TimerFired { id, name } => serde_json::json!({
+                "event": "timer_fired",
+                "id": id,
+                "name": name,
+            }),
+            Event::Paused(duration) => serde_json::json!({
+                "event": "paused",
+                "duration": duration.as_secs(),
+            }),
+            Event::Resumed => serde_json::json!({ "event": "resumed" }),
+            Event::Toggled { enabled } => serde_json::json!({
+                "event": "toggled",
+                "enabled": enabled,
+            }),
+            Event::Reset(reason) => serde_json::json!({
+                "event": "reset",
+                "reason": reason,
+            }),
+            Event::Frozen => serde_json::json!({ "event": "frozen" }),
+            Event::Unfrozen => serde_json::json!({ "event": "unfrozen" }),
+            Event::ActivityLost => serde_json::json!({ "event": "activity_lost" }),
+            Event::ActivityRestored => serde_json::json!({ "event": "activity_restored" }),
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Status(status) => write!(f, "{status}"),
+            Event::TimerFired { name, .. } => write!(f, "Timer '{name}' fired"),
+            Event::Paused(duration) => write!(f, "Paused for {}", duration.display()),
+            Event::Resumed => f.write_str("Resumed"),
+            Event::Toggled { enabled: true } => f.write_str("Enabled"),
+            Event::Toggled { enabled: false } => f.write_str("Disabled"),
+            Event::Reset(reason) => write!(f, "Reset ({reason:?})"),
+            Event::Frozen => f.write_str("Frozen because of inactivity"),
+            Event::Unfrozen => f.write_str("Unfrozen"),
+            Event::ActivityLost => f.write_str("Activity source disconnected"),
+            Event::ActivityRestored => f.write_str("Activity source reconnected"),
+        }
+    }
+}
+
 pub trait DurationExt {
     fn display(&self) -> DurationDisplay;
 }
@@ -110,3 +597,19 @@ fn format_duration(f: &mut fmt::Formatter, duration: Duration) -> fmt::Result {
     }
     write!(f, "{:02}:{:02}", minutes, secs)
 }
+
+#[test]
+fn activity_message_test() {
+    let mut activity = ActivityMessage::any(100);
+    activity.record(InputKind::Pointer, 160);
+    activity.record(InputKind::Keyboard, 130);
+    assert_eq!(activity.last_input, 160);
+    assert_eq!(activity.last_input_of(&[InputKind::Keyboard]), Some(130));
+    assert_eq!(
+        activity.last_input_of(&[InputKind::Keyboard, InputKind::Touch]),
+        Some(130)
+    );
+    // A version 1 actived sends its activity instead of a hello
+    let bytes = rmp_serde::to_vec(&(200u64,)).unwrap();
+    assert!(rmp_serde::from_slice::<ActivedHello>(&bytes).is_err());
+}
@@ -0,0 +1,187 @@
+use jiff::{
+    Zoned,
+    civil::{Time, Weekday},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr, time::Duration};
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Weekdays and local time windows in which a timer is active, e.g. `mon-fri 09:00-17:30`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ScheduleRule {
+    /// Active days, indexed from monday
+    days: [bool; 7],
+    windows: Vec<(Time, Time)>,
+    source: String,
+}
+
+impl ScheduleRule {
+    fn contains(&self, now: &Zoned) -> bool {
+        self.days[weekday_index(now.weekday())]
+            && self
+                .windows
+                .iter()
+                .any(|(start, end)| *start <= now.time() && now.time() < *end)
+    }
+}
+
+fn weekday_index(weekday: Weekday) -> usize {
+    weekday.to_monday_zero_offset() as usize
+}
+
+fn parse_weekday(str: &str) -> Result<usize, String> {
+    WEEKDAYS
+        .iter()
+        .position(|name| str.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("invalid weekday '{str}', expected one of mon, tue, ..., sun"))
+}
+
+fn parse_days(str: &str) -> Result<[bool; 7], String> {
+    let mut days = [false; 7];
+    for part in str.split(',') {
+        if let Some((from, to)) = part.split_once('-') {
+            let (from, to) = (parse_weekday(from)?, parse_weekday(to)?);
+            // Ranges may wrap around the end of the week, e.g. 'sat-mon'
+            let mut day = from;
+            loop {
+                days[day] = true;
+                if day == to {
+                    break;
+                }
+                day = (day + 1) % 7;
+            }
+        } else {
+            days[parse_weekday(part)?] = true;
+        }
+    }
+    Ok(days)
+}
+
+fn parse_window(str: &str) -> Result<(Time, Time), String> {
+    let (start, end) = str
+        .split_once('-')
+        .ok_or_else(|| format!("invalid time window '{str}', expected 'hh:mm-hh:mm'"))?;
+    let start: Time = start
+        .parse()
+        .map_err(|e| format!("invalid start time '{start}': {e}"))?;
+    let end: Time = end
+        .parse()
+        .map_err(|e| format!("invalid end time '{end}': {e}"))?;
+    if end <= start {
+        return Err(format!("time window '{str}' must end after it starts"));
+    }
+    Ok((start, end))
+}
+
+impl FromStr for ScheduleRule {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let mut parts = str.split_whitespace().peekable();
+        // The days are optional, without them the rule applies to every day
+        let days = match parts.peek() {
+            Some(first) if !first.starts_with(|c: char| c.is_ascii_digit()) => {
+                let days = parse_days(first)?;
+                parts.next();
+                days
+            }
+            _ => [true; 7],
+        };
+        let windows = parts.map(parse_window).collect::<Result<Vec<_>, _>>()?;
+        if windows.is_empty() {
+            return Err(format!("schedule '{str}' has no time windows"));
+        }
+        Ok(Self {
+            days,
+            windows,
+            source: str.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for ScheduleRule {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ScheduleRule> for String {
+    fn from(value: ScheduleRule) -> Self {
+        value.source
+    }
+}
+
+impl fmt::Display for ScheduleRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// A timer schedule, the timer is active when any of the rules matches
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Schedule(pub Vec<ScheduleRule>);
+
+impl Schedule {
+    pub fn is_active(&self, now: &Zoned) -> bool {
+        self.0.iter().any(|rule| rule.contains(now))
+    }
+
+    /// Returns the time until the schedule becomes active, zero when it is active now
+    pub fn time_until_active(&self, now: &Zoned) -> Option<Duration> {
+        if self.is_active(now) {
+            return Some(Duration::ZERO);
+        }
+        // Look up to a week ahead for the first window that starts
+        let mut next: Option<Zoned> = None;
+        for offset in 0..=7 {
+            let Ok(date) = now.date().checked_add(jiff::Span::new().days(offset)) else {
+                break;
+            };
+            let weekday = weekday_index(date.weekday());
+            for rule in self.0.iter().filter(|rule| rule.days[weekday]) {
+                for (start, _) in &rule.windows {
+                    let Ok(start) = date.to_datetime(*start).to_zoned(now.time_zone().clone())
+                    else {
+                        continue;
+                    };
+                    if start > *now && next.as_ref().is_none_or(|next| start < *next) {
+                        next = Some(start);
+                    }
+                }
+            }
+            if next.is_some() {
+                break;
+            }
+        }
+        next.and_then(|next| Duration::try_from(now.duration_until(&next)).ok())
+    }
+}
+
+#[test]
+fn schedule_test() {
+    let schedule: Schedule = Schedule(vec![
+        "mon-fri 09:00-12:00 13:00-17:30".parse().unwrap(),
+        "sat-sun 10:00-11:00".parse().unwrap(),
+    ]);
+    let at = |str: &str| -> Zoned { format!("{str}[UTC]").parse().unwrap() };
+    // 2026-10-16 is a friday
+    assert!(schedule.is_active(&at("2026-10-16T09:00")));
+    assert!(!schedule.is_active(&at("2026-10-16T12:30")));
+    assert!(!schedule.is_active(&at("2026-10-16T17:30")));
+    assert_eq!(
+        schedule.time_until_active(&at("2026-10-16T12:30")),
+        Some(Duration::from_secs(30 * 60))
+    );
+    assert_eq!(
+        schedule.time_until_active(&at("2026-10-16T18:00")),
+        Some(Duration::from_secs(16 * 3600))
+    );
+    assert!("mon-fri".parse::<ScheduleRule>().is_err());
+    assert!("fri 17:00-09:00".parse::<ScheduleRule>().is_err());
+    assert!("mo 09:00-17:00".parse::<ScheduleRule>().is_err());
+}
@@ -0,0 +1,57 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, time::Duration};
+
+/// Daemon state that is saved to disk so that it survives a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct State {
+    /// Unix time at which the state was saved
+    pub saved_at: u64,
+    pub elapsed: Duration,
+    pub next_timer_at: Duration,
+    pub next_timer: Option<usize>,
+    pub is_enabled: bool,
+    pub paused_until: Option<u64>,
+    pub timers: Vec<TimerProgress>,
+}
+
+/// Progress of a single timer, matched with the configured timers by position and id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimerProgress {
+    pub id: String,
+    pub is_enabled: bool,
+    pub prompts: u64,
+    pub snoozes: u32,
+    /// Elapsed time at which a snoozed timer fires
+    pub snoozed_until: Option<Duration>,
+    pub reset_at: Duration,
+    /// Key presses since the last reset, for typing timers
+    #[serde(default)]
+    pub keystrokes: u64,
+    /// Unix time in milliseconds at which the current typing streak started
+    pub streak_start: Option<u64>,
+}
+
+impl State {
+    /// Loads the state file, returns `None` if there is no saved state
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let state_str = fs::read_to_string(path).context("failed to read state file")?;
+        let state = serde_yaml_ng::from_str(&state_str).context("failed to parse state file")?;
+        Ok(Some(state))
+    }
+
+    /// Writes the state to a temporary file first, such that a crash never leaves a partial file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("failed to create state directory")?;
+        }
+        let state_str = serde_yaml_ng::to_string(self).context("failed to serialize state")?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, state_str).context("failed to write state file")?;
+        fs::rename(&tmp_path, path).context("failed to replace state file")?;
+        Ok(())
+    }
+}
@@ -23,34 +23,76 @@ fn format_string_test() {
     assert_eq!(format_string("A & {}", "B"), "A & B");
 }
 
+/// Quotes an argument for the shell, such that it is passed as a single word
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+#[test]
+fn shell_quote_test() {
+    assert_eq!(shell_quote("20:00"), "'20:00'");
+    assert_eq!(shell_quote("it's $HOME"), "'it'\\''s $HOME'");
+}
+
+fn build_notification(title: &str, description: &str, timeout: Option<u32>) -> Notification {
+    Notification::new()
+        .appname("blink")
+        .summary(title)
+        .body(description)
+        .timeout(if let Some(timeout) = timeout {
+            if timeout == 0 {
+                Timeout::Never
+            } else {
+                Timeout::Milliseconds(timeout * 1000)
+            }
+        } else {
+            Timeout::Default
+        })
+        .urgency(if timeout == Some(0) {
+            Urgency::Critical
+        } else {
+            Urgency::Normal
+        })
+        .finalize()
+}
+
 /// Displays a notification with the break info
 pub fn show_notification(title: String, description: String, timeout: Option<u32>) {
     thread::spawn(move || {
-        if let Err(e) = Notification::new()
-            .appname("blink")
-            .summary(&title)
-            .body(&description)
-            .timeout(if let Some(timeout) = timeout {
-                if timeout == 0 {
-                    Timeout::Never
-                } else {
-                    Timeout::Milliseconds(timeout * 1000)
-                }
-            } else {
-                Timeout::Default
-            })
-            .urgency(if timeout == Some(0) {
-                Urgency::Critical
-            } else {
-                Urgency::Normal
-            })
-            .show()
-        {
+        if let Err(e) = build_notification(&title, &description, timeout).show() {
             log::error!("Failed to show notification: {e}");
         }
     });
 }
 
+/// Displays a notification with buttons given as (identifier, label), `on_action` is called
+/// with the identifier of the clicked button
+pub fn show_notification_with_actions<F>(
+    title: String,
+    description: String,
+    timeout: Option<u32>,
+    actions: Vec<(String, String)>,
+    on_action: F,
+) where
+    F: FnOnce(&str) + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut notification = build_notification(&title, &description, timeout);
+        for (id, label) in &actions {
+            notification.action(id, label);
+        }
+        match notification.show() {
+            Ok(handle) => handle.wait_for_action(|action| {
+                // The notification was closed without clicking a button
+                if action != "__closed" {
+                    on_action(action);
+                }
+            }),
+            Err(e) => log::error!("Failed to show notification: {e}"),
+        }
+    });
+}
+
 /// Runs a command in a new thread, output is logged when unsuccesful
 pub fn exec_command(command: String) {
     thread::spawn(move || {